@@ -9,14 +9,16 @@ use std::env;
 use std::error::Error as StdError;
 
 use lints;
+use printer;
 use ripgrep_stolen::atty;
 use errors;
 
 static CWD: &'static str = "./";
 static STDIN: &'static str = "-";
-static DEFAULT_CONFIG_FILE: &'static str = "relint.toml";
+pub static DEFAULT_CONFIG_FILE: &'static str = "relint.toml";
 
 #[derive(Debug)]
+#[derive(Clone)]
 #[derive(Eq, PartialEq)]
 pub struct SearchInput {
     pub paths: Vec<path::PathBuf>,
@@ -27,14 +29,21 @@ pub struct SearchInput {
     pub no_ignore_vcs: bool,
     pub maxdepth: Option<usize>,
     pub threads: usize,
+    pub sort: bool,
+    /// Path to match types against and report for stdin
+    pub stdin_filename: Option<path::PathBuf>,
 }
 
 impl SearchInput {
     fn from_args(matches: &clap::ArgMatches) -> Result<SearchInput, errors::ArgumentError> {
-        let paths = match matches.values_of("path") {
-            None => vec![default_path()],
-            Some(vals) => vals.map(|p| path::Path::new(p).to_path_buf()).collect(),
-        };
+        let stdin_filename = matches.value_of("stdin-filename").map(|p| path::Path::new(p).to_path_buf());
+        if stdin_filename.is_some() && 0 < matches.occurrences_of("path") {
+            let err = clap::Error::with_description("The argument '--stdin-filename <PATH>' cannot \
+                                                     be used with paths",
+                                                    clap::ErrorKind::ArgumentConflict);
+            return Err(From::from(err));
+        }
+        let paths = paths_of(matches, !atty::on_stdin() && atty::stdin_is_readable());
         let source = SearchInput {
             paths: paths,
             follow: matches.is_present("follow"),
@@ -44,22 +53,29 @@ impl SearchInput {
             no_ignore_vcs: matches.is_present("no-ignore-vcs"),
             maxdepth: parsed_value_of(matches, "maxdepth")?,
             threads: parsed_value_of(matches, "threads")?.unwrap_or(0),
+            sort: !matches.is_present("no-sort"),
+            stdin_filename: stdin_filename,
         };
         Ok(source)
     }
 
     /// If true, ignore threads
     pub fn is_one_path(&self) -> bool {
-        self.paths.len() == 1 && self.paths[0].is_file()
+        self.paths.len() == 1 && (self.paths[0].is_file() || self.paths[0] == path::Path::new(STDIN))
     }
 }
 
 #[derive(Debug)]
+#[derive(Clone, Copy)]
 #[derive(Eq, PartialEq)]
 pub enum SearchOutput {
     None,
     Message,
     File { matched: bool },
+    /// Number of findings per file
+    Count,
+    /// Number of findings per lint
+    CountByLint,
 }
 
 impl SearchOutput {
@@ -70,6 +86,10 @@ impl SearchOutput {
             SearchOutput::File { matched: true }
         } else if matches.is_present("files-without-errors") {
             SearchOutput::File { matched: false }
+        } else if matches.is_present("count") {
+            SearchOutput::Count
+        } else if matches.is_present("count-by-lint") {
+            SearchOutput::CountByLint
         } else {
             SearchOutput::Message
         };
@@ -78,31 +98,120 @@ impl SearchOutput {
     }
 }
 
+#[derive(Debug)]
+#[derive(Eq, PartialEq)]
+pub enum FixMode {
+    Write,
+    Diff,
+    Interactive,
+}
+
+impl FixMode {
+    fn from_args(matches: &clap::ArgMatches) -> Result<FixMode, errors::ArgumentError> {
+        let mode = if matches.is_present("dry-run") {
+            FixMode::Diff
+        } else if matches.is_present("interactive") {
+            FixMode::Interactive
+        } else {
+            FixMode::Write
+        };
+
+        Ok(mode)
+    }
+}
+
+/// Where to find the lines to limit findings to.
+#[derive(Debug)]
+#[derive(Eq, PartialEq)]
+pub enum DiffSource {
+    /// Working tree changes relative to a git revision
+    Git(String),
+    /// A unified diff, `-` for stdin
+    File(path::PathBuf),
+}
+
+impl DiffSource {
+    fn from_args(matches: &clap::ArgMatches) -> Option<DiffSource> {
+        if let Some(rev) = matches.value_of("diff-base") {
+            Some(DiffSource::Git(rev.to_string()))
+        } else {
+            matches.value_of("diff-file").map(|p| DiffSource::File(path::Path::new(p).to_path_buf()))
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Action {
     Search {
         input: SearchInput,
         min_severity: lints::ErrorLevel,
+        min_reported: lints::ErrorLevel,
         output: SearchOutput,
+        stats: bool,
+        report_unused_disables: bool,
+        baseline: Option<path::PathBuf>,
+        diff: Option<DiffSource>,
+        /// Lint the content staged in the git index
+        staged: bool,
+        /// Re-lint files as they change
+        watch: bool,
+    },
+    WriteBaseline {
+        input: SearchInput,
+        min_reported: lints::ErrorLevel,
+        report_unused_disables: bool,
+        path: path::PathBuf,
+    },
+    Fix {
+        input: SearchInput,
+        min_reported: lints::ErrorLevel,
+        mode: FixMode,
     },
     PrintTypes,
+    /// Serve diagnostics and fixes over the Language Server Protocol
+    Lsp,
 }
 
 impl Action {
     fn from_args(matches: &clap::ArgMatches) -> Result<Action, errors::ArgumentError> {
-        let action = if matches.is_present("type-list") {
+        let action = if matches.subcommand_matches("lsp").is_some() {
+            Action::Lsp
+        } else if matches.is_present("type-list") {
             Action::PrintTypes
+        } else if let Some(path) = matches.value_of("write-baseline") {
+            let input = SearchInput::from_args(matches)?;
+            let min_reported = level_of(matches, "report-level");
+            Action::WriteBaseline {
+                input: input,
+                min_reported: min_reported,
+                report_unused_disables: matches.is_present("report-unused-disables"),
+                path: path::Path::new(path).to_path_buf(),
+            }
+        } else if matches.is_present("fix") {
+            let input = SearchInput::from_args(matches)?;
+            let min_reported = level_of(matches, "report-level");
+            let mode = FixMode::from_args(matches)?;
+            Action::Fix {
+                input: input,
+                min_reported: min_reported,
+                mode: mode,
+            }
         } else {
             let input = SearchInput::from_args(matches)?;
-            let min_severity = matches.value_of("error-level")
-                .expect("Default should cover this")
-                .parse::<lints::ErrorLevel>()
-                .expect("Should be validated");
+            let min_severity = level_of(matches, "error-level");
+            let min_reported = level_of(matches, "report-level");
             let output = SearchOutput::from_args(matches)?;
             Action::Search {
                 input: input,
                 min_severity: min_severity,
+                min_reported: min_reported,
                 output: output,
+                stats: matches.is_present("stats"),
+                report_unused_disables: matches.is_present("report-unused-disables"),
+                baseline: matches.value_of("baseline").map(|p| path::Path::new(p).to_path_buf()),
+                diff: DiffSource::from_args(matches),
+                staged: matches.is_present("staged"),
+                watch: matches.is_present("watch"),
             }
         };
 
@@ -114,13 +223,36 @@ impl Action {
 pub struct Printer {
     pub quiet: bool,
     pub null: bool,
+    pub format: printer::Format,
+    pub color: bool,
+    pub before_context: usize,
+    pub after_context: usize,
 }
 
 impl Printer {
     fn from_args(matches: &clap::ArgMatches) -> Result<Printer, errors::ArgumentError> {
+        let format = match matches.value_of("format").unwrap_or("human") {
+            "github" => printer::Format::Github,
+            "gitlab" => printer::Format::Gitlab,
+            "json" => printer::Format::Json,
+            "sarif" => printer::Format::Sarif,
+            "checkstyle" => printer::Format::Checkstyle,
+            "junit" => printer::Format::Junit,
+            _ => printer::Format::Human,
+        };
+        let color = match matches.value_of("color").expect("Default should cover this") {
+            "always" => true,
+            "never" => false,
+            _ => atty::on_stdout(),
+        };
+        let context = parsed_value_of(matches, "context")?.unwrap_or(0);
         Ok(Printer {
             quiet: matches.is_present("quiet"),
             null: matches.is_present("null"),
+            format: format,
+            color: color,
+            before_context: parsed_value_of(matches, "before-context")?.unwrap_or(context),
+            after_context: parsed_value_of(matches, "after-context")?.unwrap_or(context),
         })
     }
 }
@@ -129,14 +261,18 @@ impl Printer {
 pub struct App {
     pub action: Action,
     pub printer: Printer,
-    pub lint_path: path::PathBuf,
+    /// Only `None` for the LSP server, which looks for lints per workspace folder
+    pub lint_path: Option<path::PathBuf>,
 }
 
 impl App {
     pub fn from_args(matches: &clap::ArgMatches) -> Result<App, errors::ArgumentError> {
         let action = Action::from_args(matches)?;
         let printer = Printer::from_args(matches)?;
-        let lint_path = get_project_file(matches, "lints", DEFAULT_CONFIG_FILE)?;
+        let lint_path = match action {
+            Action::Lsp => matches.value_of("lints").map(|p| path::Path::new(p).to_path_buf()),
+            _ => Some(get_project_file(matches, "lints", DEFAULT_CONFIG_FILE)?),
+        };
 
         Ok(App {
             action: action,
@@ -177,6 +313,13 @@ fn parsed_value_of<F: str::FromStr>(matches: &clap::ArgMatches,
     }
 }
 
+fn level_of(matches: &clap::ArgMatches, name: &str) -> lints::ErrorLevel {
+    matches.value_of(name)
+        .expect("Default should cover this")
+        .parse::<lints::ErrorLevel>()
+        .expect("Should be validated")
+}
+
 fn build_app<'a>() -> clap::App<'a, 'a> {
     let mut args = clap::App::new("relint")
         .version(crate_version!())
@@ -186,9 +329,15 @@ fn build_app<'a>() -> clap::App<'a, 'a> {
     args = args.arg(arg("path")
             .multiple(true)
             .default_value(CWD)
-            .help("Specify '-' for stdin"))
+            .help("Specify '-' for stdin, the default when it's piped in"))
         .group(clap::ArgGroup::with_name("Paths")
-            .args(&["follow", "hidden", "no-ignore", "no-ignore-vcs", "maxdepth", "threads"])
+            .args(&["follow",
+                    "hidden",
+                    "no-ignore",
+                    "no-ignore-vcs",
+                    "maxdepth",
+                    "threads",
+                    "no-sort"])
             .multiple(true))
         .arg(flag("follow")
             .short("L")
@@ -202,28 +351,104 @@ fn build_app<'a>() -> clap::App<'a, 'a> {
             .help("Descend at most NUM directories."))
         .arg(option("threads", "NUM")
             .short("j")
-            .validator(validate_number));
+            .validator(validate_number)
+            .help("Number of threads to lint with (0 picks automatically)."))
+        .arg(flag("no-sort").help("Print results as files finish instead of sorted by path."));
 
     args = args.arg(option("lints", "FILE")
         .short("c")
         .help("Lints (searches up path if not specified)"));
 
     args = args.group(clap::ArgGroup::with_name("PrintNames")
-            .args(&["files", "files-with-errors", "files-without-errors"]))
+            .args(&["files",
+                    "files-with-errors",
+                    "files-without-errors",
+                    "count",
+                    "count-by-lint"]))
         .arg(flag("files-with-errors")
             .short("l")
             .help("Only show the path of each file with at least one match."))
         .arg(flag("files-without-errors")
             .help("Only show the path of each file that contains zero matches."))
         .arg(flag("files").help("Print each file that would be searched."))
+        .arg(flag("count").help("Only show the number of findings in each file."))
+        .arg(flag("count-by-lint").help("Only show the number of findings of each lint."))
         .arg(flag("null")
             .requires("PrintNames")
-            .help("Print NUL byte after file names"));
+            .help("Print NUL byte after file names"))
+        .arg(option("format", "FORMAT")
+            .possible_values(&["human", "github", "gitlab", "json", "sarif", "checkstyle", "junit"])
+            .conflicts_with_all(&["PrintNames", "fix"])
+            .help("How to print lint messages"))
+        .arg(flag("stats")
+            .conflicts_with_all(&["fix", "type-list"])
+            .help("Print statistics about the lint run."))
+        .arg(flag("report-unused-disables")
+            .conflicts_with_all(&["fix", "type-list"])
+            .help("Report suppression comments that suppress nothing or name unknown lints."))
+        .arg(option("baseline", "FILE")
+            .conflicts_with_all(&["fix", "type-list"])
+            .help("Only report findings not recorded in FILE by --write-baseline."))
+        .arg(option("write-baseline", "FILE")
+            .conflicts_with_all(&["baseline", "fix", "type-list", "PrintNames", "format", "stats"])
+            .help("Record all current findings in FILE instead of reporting them."))
+        .arg(option("diff-base", "REV")
+            .conflicts_with_all(&["fix", "type-list", "write-baseline"])
+            .help("Only report findings on lines changed since git revision REV, or in \
+                   untracked files."))
+        .arg(option("diff-file", "FILE")
+            .conflicts_with_all(&["diff-base", "fix", "type-list", "write-baseline"])
+            .help("Only report findings on lines added by the unified diff in FILE ('-' for \
+                   stdin)."))
+        .arg(option("stdin-filename", "PATH")
+            .conflicts_with_all(&["diff-base",
+                                  "diff-file",
+                                  "staged",
+                                  "type-list",
+                                  "write-baseline"])
+            .help("Lint stdin as if it were PATH, matching lint types against and reporting \
+                   PATH."))
+        .arg(flag("staged")
+            .conflicts_with_all(&["diff-base",
+                                  "diff-file",
+                                  "fix",
+                                  "type-list",
+                                  "write-baseline"])
+            .help("Lint the content of files with staged changes, as staged in git."))
+        .arg(flag("watch")
+            .conflicts_with_all(&["diff-base",
+                                  "diff-file",
+                                  "fix",
+                                  "staged",
+                                  "stdin-filename",
+                                  "type-list",
+                                  "write-baseline"])
+            .help("Lint once, then re-lint files as they or the lints change (Linux only)."))
+        .arg(option("color", "WHEN")
+            .possible_values(&["auto", "always", "never"])
+            .default_value("auto")
+            .help("When to use colors in lint messages"))
+        .arg(option("after-context", "NUM")
+            .short("A")
+            .validator(validate_number)
+            .help("Show NUM lines after each lint message."))
+        .arg(option("before-context", "NUM")
+            .short("B")
+            .validator(validate_number)
+            .help("Show NUM lines before each lint message."))
+        .arg(option("context", "NUM")
+            .short("C")
+            .validator(validate_number)
+            .help("Show NUM lines before and after each lint message."));
 
     args = args.arg(option("error-level", "LEVEL")
-        .possible_values(&lints::ErrorLevel::variants())
-        .default_value("Error")
-        .help("Lint item level to be treated as errors"));
+            .possible_values(&lints::ErrorLevel::variants())
+            .default_value("Error")
+            .help("Lowest lint item level to be treated as errors"))
+        .arg(option("report-level", "LEVEL")
+            .possible_values(&lints::ErrorLevel::variants())
+            .default_value("Info")
+            .help("Lowest lint item level to report"));
 
     args = args.arg(flag("quiet")
             .short("q")
@@ -231,7 +456,22 @@ fn build_app<'a>() -> clap::App<'a, 'a> {
             .help("Do not print any result"))
         .arg(flag("type-list")
             .conflicts_with("PrintNames")
-            .help("Show all supported file types."));
+            .help("Show all supported file types."))
+        .arg(flag("fix")
+            .conflicts_with_all(&["PrintNames", "type-list"])
+            .help("Rewrite matches of lints that have a `replace` template."))
+        .arg(flag("dry-run")
+            .alias("diff")
+            .requires("fix")
+            .help("Print a diff of the fixes instead of writing them."))
+        .arg(flag("interactive")
+            .short("i")
+            .requires("fix")
+            .conflicts_with_all(&["dry-run", "quiet"])
+            .help("Ask before applying each fix."));
+
+    args = args.subcommand(clap::SubCommand::with_name("lsp")
+        .about("Serve diagnostics and fixes over the Language Server Protocol on stdio"));
 
     args
 }
@@ -250,7 +490,8 @@ pub fn parse_args<'a>() -> Result<Option<clap::ArgMatches<'a>>, errors::Argument
     }
 }
 
-fn find_project_file(dir: &path::Path, name: &str) -> Option<path::PathBuf> {
+/// The file called `name` in `dir` or the nearest of its parents.
+pub fn find_project_file(dir: &path::Path, name: &str) -> Option<path::PathBuf> {
     let mut file_path = dir.join(name);
     while !file_path.exists() {
         file_path.pop();
@@ -279,8 +520,67 @@ fn get_project_file(matches: &clap::ArgMatches,
     Ok(path)
 }
 
-fn default_path() -> path::PathBuf {
-    let search_cwd = atty::on_stdin() || !atty::stdin_is_readable();
-    let default_path = if search_cwd { CWD } else { STDIN };
-    path::Path::new(default_path).to_path_buf()
+/// Paths given to lint, otherwise stdin if `stdin_piped` and nothing else reads it, otherwise the
+/// current directory.
+fn paths_of(matches: &clap::ArgMatches, stdin_piped: bool) -> Vec<path::PathBuf> {
+    if matches.is_present("stdin-filename") {
+        return vec![path::Path::new(STDIN).to_path_buf()];
+    }
+    // `path` always has a value, from its default if not given
+    if 0 < matches.occurrences_of("path") {
+        return matches.values_of("path")
+            .expect("Given above")
+            .map(|p| path::Path::new(p).to_path_buf())
+            .collect();
+    }
+    // Stdin is taken by the diff or not what's being linted
+    let stdin_taken = matches.value_of("diff-file") == Some(STDIN) ||
+                      matches.is_present("diff-base") || matches.is_present("staged") ||
+                      matches.is_present("watch");
+    let default_path = if stdin_piped && !stdin_taken { STDIN } else { CWD };
+    vec![path::Path::new(default_path).to_path_buf()]
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path;
+
+    fn paths(args: &[&str], stdin_piped: bool) -> Vec<path::PathBuf> {
+        let mut argv = vec!["relint"];
+        argv.extend_from_slice(args);
+        let matches = build_app().get_matches_from_safe(argv).expect("Valid arguments");
+        paths_of(&matches, stdin_piped)
+    }
+
+    fn expected(paths: &[&str]) -> Vec<path::PathBuf> {
+        paths.iter().map(|p| path::Path::new(p).to_path_buf()).collect()
+    }
+
+    #[test]
+    fn paths_default_to_cwd() {
+        assert_eq!(paths(&[], false), expected(&["./"]));
+    }
+
+    #[test]
+    fn paths_default_to_piped_stdin() {
+        assert_eq!(paths(&[], true), expected(&["-"]));
+    }
+
+    #[test]
+    fn paths_stdin_filename() {
+        assert_eq!(paths(&["--stdin-filename", "src/a.rs"], false), expected(&["-"]));
+    }
+
+    #[test]
+    fn paths_given() {
+        assert_eq!(paths(&["src", "./"], true), expected(&["src", "./"]));
+    }
+
+    #[test]
+    fn paths_default_to_cwd_when_the_diff_is_piped() {
+        assert_eq!(paths(&["--diff-file", "-"], true), expected(&["./"]));
+        assert_eq!(paths(&["--diff-base", "HEAD"], true), expected(&["./"]));
+    }
 }
@@ -0,0 +1,172 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::io::Write;
+use std::path;
+
+use rustc_serialize::json::{self, Json, ToJson};
+
+use errors;
+use search;
+
+static VERSION: u64 = 1;
+
+/// Findings accepted as they are, so only new ones get reported.
+///
+/// Findings are keyed by lint, path and a fingerprint of the line they are on rather than by
+/// line number, so they survive lines being added or removed around them.  Paths are relative to
+/// the baseline file, so it matches wherever relint is run from.
+#[derive(Debug)]
+pub struct Baseline {
+    /// Directory paths are relative to
+    root: path::PathBuf,
+    /// Number of findings per path, then per lint name and fingerprint
+    files: BTreeMap<String, BTreeMap<(String, String), usize>>,
+}
+
+impl Baseline {
+    /// An empty baseline, to be saved to `path`.
+    pub fn new(path: &path::Path) -> Baseline {
+        let dir = match path.parent() {
+            Some(dir) if dir != path::Path::new("") => dir,
+            _ => path::Path::new("."),
+        };
+        Baseline {
+            root: dir.canonicalize().unwrap_or_else(|_| search::absolute(dir)),
+            files: BTreeMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, path: &path::Path, content: &[u8], violation: &search::Violation) {
+        let key = (violation.lint.name.clone(), fingerprint(content, violation));
+        *self.files
+            .entry(self.path_key(path))
+            .or_insert_with(BTreeMap::new)
+            .entry(key)
+            .or_insert(0) += 1;
+    }
+
+    /// Leave out `violations` recorded in the baseline.
+    ///
+    /// Each entry accepts as many findings as were recorded, so copies of an accepted line are
+    /// still reported.
+    pub fn filter(&self,
+                  path: &path::Path,
+                  content: &[u8],
+                  violations: &mut Vec<search::Violation>) {
+        let mut remaining = match self.files.get(&self.path_key(path)) {
+            Some(counts) => counts.clone(),
+            None => return,
+        };
+        violations.retain(|violation| {
+            let key = (violation.lint.name.clone(), fingerprint(content, violation));
+            match remaining.get_mut(&key) {
+                Some(count) if 0 < *count => {
+                    *count -= 1;
+                    false
+                }
+                _ => true,
+            }
+        });
+    }
+
+    pub fn load(path: &path::Path) -> Result<Baseline, errors::ConfigError> {
+        Baseline::load_file(path).map_err(|e| e.add_path(Some(path)))
+    }
+
+    fn load_file(path: &path::Path) -> Result<Baseline, errors::ConfigError> {
+        let mut f = fs::File::open(path)?;
+        let mut text = String::new();
+        f.read_to_string(&mut text)?;
+        let root = Json::from_str(&text)?;
+        let version = field(&root, "version")?;
+        let version = version.as_u64().ok_or_else(|| field_type("version", "number", version))?;
+        if version != VERSION {
+            return Err(From::from(errors::SpecificConfigError::BaselineVersion(version)));
+        }
+
+        let mut baseline = Baseline::new(path);
+        let findings = field(&root, "findings")?;
+        let findings = findings.as_array()
+            .ok_or_else(|| field_type("findings", "array", findings))?;
+        for finding in findings {
+            let lint = field_str(finding, "lint")?;
+            let path = field_str(finding, "path")?;
+            let fingerprint = field_str(finding, "fingerprint")?;
+            let count = field(finding, "count")?;
+            let count = count.as_u64()
+                .ok_or_else(|| field_type("findings[...].count", "number", count))?;
+            *baseline.files
+                .entry(path.to_string())
+                .or_insert_with(BTreeMap::new)
+                .entry((lint.to_string(), fingerprint.to_string()))
+                .or_insert(0) += count as usize;
+        }
+        Ok(baseline)
+    }
+
+    pub fn save(&self, path: &path::Path) -> Result<(), errors::ConfigError> {
+        let mut findings = Vec::new();
+        for (file, counts) in &self.files {
+            for (&(ref lint, ref fingerprint), count) in counts {
+                let mut finding = BTreeMap::new();
+                finding.insert("lint".to_string(), lint.to_json());
+                finding.insert("path".to_string(), file.to_json());
+                finding.insert("fingerprint".to_string(), fingerprint.to_json());
+                finding.insert("count".to_string(), count.to_json());
+                findings.push(Json::Object(finding));
+            }
+        }
+        let mut root = BTreeMap::new();
+        root.insert("version".to_string(), VERSION.to_json());
+        root.insert("findings".to_string(), Json::Array(findings));
+        let text = format!("{}\n", json::as_pretty_json(&Json::Object(root)));
+
+        fs::File::create(path)
+            .and_then(|mut f| f.write_all(text.as_bytes()))
+            .map_err(|e| errors::ConfigError::from(e).add_path(Some(path)))
+    }
+
+    fn path_key(&self, path: &path::Path) -> String {
+        let path = search::absolute(path);
+        path.strip_prefix(&self.root).unwrap_or(&path).to_string_lossy().into_owned()
+    }
+}
+
+/// Hash of the line the violation starts on, ignoring changes in whitespace.
+fn fingerprint(content: &[u8], violation: &search::Violation) -> String {
+    let line_start = violation.start - (violation.column - 1);
+    let line_end = content[line_start..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map(|i| line_start + i)
+        .unwrap_or(content.len());
+    let hash = search::fnv1a_normalized(search::FNV_OFFSET, &content[line_start..line_end]);
+    format!("{:016x}", hash)
+}
+
+fn field<'j>(entry: &'j Json, name: &str) -> Result<&'j Json, errors::FieldError> {
+    entry.find(name)
+        .ok_or_else(|| errors::FieldError::new(name, errors::SpecificFieldError::MissingField))
+}
+
+fn field_str<'j>(entry: &'j Json, name: &str) -> Result<&'j str, errors::FieldError> {
+    let value = field(entry, name)?;
+    value.as_string().ok_or_else(|| field_type(name, "string", value))
+}
+
+fn field_type(name: &str, expected: &str, actual: &Json) -> errors::FieldError {
+    let actual = match *actual {
+        Json::I64(_) | Json::U64(_) | Json::F64(_) => "number",
+        Json::String(_) => "string",
+        Json::Boolean(_) => "boolean",
+        Json::Array(_) => "array",
+        Json::Object(_) => "object",
+        Json::Null => "null",
+    };
+    errors::FieldError::new(name,
+                            errors::SpecificFieldError::FieldType {
+                                expected: expected.to_string(),
+                                actual: actual.to_string(),
+                            })
+}
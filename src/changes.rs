@@ -0,0 +1,239 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path;
+use std::str;
+
+use git;
+use search;
+
+/// Lines added or modified per file, according to a unified diff.
+#[derive(Debug, Default)]
+pub struct Changes {
+    /// 1-based, inclusive line ranges in the new version of each file, by absolute path once
+    /// read from git or a file
+    files: BTreeMap<path::PathBuf, Vec<(usize, usize)>>,
+}
+
+impl Changes {
+    /// Changes in the working tree relative to `rev`, with paths relative to the current
+    /// directory.
+    ///
+    /// Untracked files that aren't ignored are new to git, so all of their lines count as
+    /// changed.
+    pub fn from_git(rev: &str) -> io::Result<Changes> {
+        // Fixed prefixes, whatever `diff.noprefix` or `diff.mnemonicPrefix` say
+        let diff = git::output(&["diff",
+                                 "--relative",
+                                 "--no-color",
+                                 "--no-ext-diff",
+                                 "--src-prefix=a/",
+                                 "--dst-prefix=b/",
+                                 "--unified=0",
+                                 rev,
+                                 "--"])?;
+        let mut changes = Changes::parse(&diff);
+        for path in git::untracked_paths()? {
+            changes.files.insert(path, vec![(1, usize::max_value())]);
+        }
+        Ok(changes.absolute())
+    }
+
+    /// Changes from a unified diff in `path`, or stdin for `-`.
+    pub fn from_file(path: &path::Path) -> io::Result<Changes> {
+        let mut diff = Vec::new();
+        if path == path::Path::new("-") {
+            let stdin = io::stdin();
+            let mut stdin = stdin.lock();
+            stdin.read_to_end(&mut diff)?;
+        } else {
+            let mut f = fs::File::open(path)?;
+            f.read_to_end(&mut diff)?;
+        }
+        Ok(Changes::parse(&diff).absolute())
+    }
+
+    /// Parse a unified diff, with paths like `git diff` (`b/` prefixed or not).
+    pub fn parse(diff: &[u8]) -> Changes {
+        let mut changes = Changes::default();
+        let mut current: Option<path::PathBuf> = None;
+        // Lines left in the new side of the current hunk and the next line number
+        let mut remaining = 0;
+        let mut line = 0;
+        for text in diff.split(|b| *b == b'\n') {
+            if 0 < remaining {
+                match text.first() {
+                    Some(&b'+') => {
+                        if let Some(ref path) = current {
+                            changes.add(path, line);
+                        }
+                        line += 1;
+                        remaining -= 1;
+                    }
+                    Some(&b'-') | Some(&b'\\') => {}
+                    _ => {
+                        line += 1;
+                        remaining -= 1;
+                    }
+                }
+            } else if text.starts_with(b"+++ ") {
+                let name = String::from_utf8_lossy(&text[4..]);
+                let name = name.split('\t').next().unwrap_or("").trim_right();
+                current = if name == "/dev/null" {
+                    None
+                } else {
+                    let name = if name.starts_with("b/") { &name[2..] } else { name };
+                    Some(path::Path::new(name).to_path_buf())
+                };
+            } else if text.starts_with(b"@@ ") {
+                if let Some((start, count)) = parse_new_range(text) {
+                    line = start;
+                    remaining = count;
+                }
+            }
+        }
+        changes
+    }
+
+    /// The same changes with paths made absolute, so they match however a path is spelled.
+    fn absolute(self) -> Changes {
+        Changes {
+            files: self.files
+                .into_iter()
+                .map(|(path, ranges)| (search::absolute(&path), ranges))
+                .collect(),
+        }
+    }
+
+    fn add(&mut self, path: &path::Path, line: usize) {
+        let ranges = self.files.entry(path.to_path_buf()).or_insert_with(Vec::new);
+        if let Some(last) = ranges.last_mut() {
+            if last.1 + 1 == line {
+                last.1 = line;
+                return;
+            }
+        }
+        ranges.push((line, line));
+    }
+
+    /// Whether any line of `path` was added or modified.
+    pub fn touches(&self, path: &path::Path) -> bool {
+        self.files.contains_key(&search::absolute(path))
+    }
+
+    /// Whether `line` of `path` was added or modified.
+    pub fn contains(&self, path: &path::Path, line: usize) -> bool {
+        self.files
+            .get(&search::absolute(path))
+            .map(|ranges| ranges.iter().any(|&(first, last)| first <= line && line <= last))
+            .unwrap_or(false)
+    }
+}
+
+/// Start and length of the new side of a `@@ -a,b +c,d @@` hunk header.
+fn parse_new_range(header: &[u8]) -> Option<(usize, usize)> {
+    let header = match str::from_utf8(header) {
+        Ok(header) => header,
+        Err(_) => return None,
+    };
+    let new = match header.split(' ').find(|part| part.starts_with('+')) {
+        Some(new) => new,
+        None => return None,
+    };
+    let mut parts = new[1..].splitn(2, ',');
+    let start = parts.next().and_then(|start| start.parse().ok());
+    let count = match parts.next() {
+        Some(count) => count.parse().ok(),
+        None => Some(1),
+    };
+    match (start, count) {
+        (Some(start), Some(count)) => Some((start, count)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::path;
+
+    fn lines(changes: &Changes, path: &str) -> Vec<(usize, usize)> {
+        changes.files.get(path::Path::new(path)).cloned().unwrap_or_else(Vec::new)
+    }
+
+    #[test]
+    fn parse_tracks_new_line_numbers() {
+        let diff = b"diff --git a/src/a.rs b/src/a.rs\n\
+                     --- a/src/a.rs\n\
+                     +++ b/src/a.rs\n\
+                     @@ -2,2 +2,3 @@\n\
+                     -old\n\
+                     +new\n\
+                     +newer\n\
+                     \x20context\n\
+                     @@ -10,0 +12,1 @@\n\
+                     +added\n";
+        let changes = Changes::parse(diff);
+        assert_eq!(lines(&changes, "src/a.rs"), vec![(2, 3), (12, 12)]);
+        let changes = changes.absolute();
+        assert!(changes.touches(path::Path::new("src/a.rs")));
+        assert!(!changes.touches(path::Path::new("src/b.rs")));
+        assert!(changes.contains(path::Path::new("./src/a.rs"), 3));
+        assert!(!changes.contains(path::Path::new("src/a.rs"), 4));
+    }
+
+    #[test]
+    fn parse_hunk_without_counts() {
+        let diff = b"--- a/a.txt\n\
+                     +++ b/a.txt\n\
+                     @@ -3 +3 @@\n\
+                     -old\n\
+                     +new\n";
+        assert_eq!(lines(&Changes::parse(diff), "a.txt"), vec![(3, 3)]);
+    }
+
+    #[test]
+    fn parse_skips_deleted_files() {
+        let diff = b"--- a/gone.txt\n\
+                     +++ /dev/null\n\
+                     @@ -1,2 +0,0 @@\n\
+                     -one\n\
+                     -two\n\
+                     --- a/kept.txt\n\
+                     +++ b/kept.txt\n\
+                     @@ -1,0 +1 @@\n\
+                     +one\n";
+        let changes = Changes::parse(diff);
+        assert!(changes.files.get(path::Path::new("/dev/null")).is_none());
+        assert_eq!(changes.files.keys().collect::<Vec<_>>(), vec![path::Path::new("kept.txt")]);
+        assert_eq!(lines(&changes, "kept.txt"), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn parse_no_newline_marker_is_not_a_line() {
+        let diff = b"--- a/a.txt\n\
+                     +++ b/a.txt\n\
+                     @@ -1,2 +1,2 @@\n\
+                     -last\n\
+                     \\ No newline at end of file\n\
+                     +last\n\
+                     +more\n\
+                     \\ No newline at end of file\n";
+        assert_eq!(lines(&Changes::parse(diff), "a.txt"), vec![(1, 2)]);
+    }
+
+    #[test]
+    fn parse_content_lines_that_look_like_headers() {
+        let diff = b"--- a/a.txt\n\
+                     +++ b/a.txt\n\
+                     @@ -1,1 +1,3 @@\n\
+                     ++++ b/other.txt\n\
+                     +@@ -1 +1 @@\n\
+                     \x20context\n";
+        let changes = Changes::parse(diff);
+        assert_eq!(changes.files.len(), 1);
+        assert_eq!(lines(&changes, "a.txt"), vec![(1, 2)]);
+    }
+}
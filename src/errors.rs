@@ -8,22 +8,28 @@ use std::io;
 use ignore;
 use toml;
 use grep;
+use regex;
+use rustc_serialize::json;
 
 #[derive(Debug)]
 pub enum ArgumentError {
     Clap(clap::Error),
+    /// Reading something an argument refers to, like a diff
+    Io(io::Error),
 }
 
 impl error::Error for ArgumentError {
     fn description(&self) -> &str {
         match *self {
             ArgumentError::Clap(ref err) => err.description(),
+            ArgumentError::Io(ref err) => err.description(),
         }
     }
 
     fn cause(&self) -> Option<&error::Error> {
         match *self {
             ArgumentError::Clap(ref err) => Some(err),
+            ArgumentError::Io(ref err) => Some(err),
         }
     }
 }
@@ -32,6 +38,7 @@ impl fmt::Display for ArgumentError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             ArgumentError::Clap(ref err) => err.fmt(f),
+            ArgumentError::Io(ref err) => err.fmt(f),
         }
     }
 }
@@ -42,6 +49,12 @@ impl From<clap::Error> for ArgumentError {
     }
 }
 
+impl From<io::Error> for ArgumentError {
+    fn from(err: io::Error) -> ArgumentError {
+        ArgumentError::Io(err)
+    }
+}
+
 #[derive(Debug)]
 pub enum SpecificFieldError {
     FieldType { expected: String, actual: String },
@@ -137,6 +150,10 @@ pub enum SpecificConfigError {
     Io(io::Error),
     Toml(toml::ParserError),
     Ignore(ignore::Error),
+    Regex(regex::Error),
+    Json(json::ParserError),
+    /// A baseline written in a format this version doesn't know
+    BaselineVersion(u64),
 }
 
 impl error::Error for SpecificConfigError {
@@ -150,6 +167,9 @@ impl error::Error for SpecificConfigError {
             SpecificConfigError::Io(ref err) => Some(err),
             SpecificConfigError::Toml(ref err) => Some(err),
             SpecificConfigError::Ignore(ref err) => Some(err),
+            SpecificConfigError::Regex(ref err) => Some(err),
+            SpecificConfigError::Json(ref err) => Some(err),
+            SpecificConfigError::BaselineVersion(_) => None,
         }
     }
 }
@@ -161,6 +181,11 @@ impl fmt::Display for SpecificConfigError {
             SpecificConfigError::Field(ref err) => err.fmt(f),
             SpecificConfigError::Toml(ref err) => err.fmt(f),
             SpecificConfigError::Ignore(ref err) => err.fmt(f),
+            SpecificConfigError::Regex(ref err) => err.fmt(f),
+            SpecificConfigError::Json(ref err) => err.fmt(f),
+            SpecificConfigError::BaselineVersion(version) => {
+                write!(f, "Unsupported baseline version '{}'", version)
+            }
         }
     }
 }
@@ -199,6 +224,15 @@ impl fmt::Display for ConfigError {
     }
 }
 
+impl From<SpecificConfigError> for ConfigError {
+    fn from(err: SpecificConfigError) -> ConfigError {
+        ConfigError {
+            file: None,
+            error: err,
+        }
+    }
+}
+
 impl From<FieldError> for ConfigError {
     fn from(err: FieldError) -> ConfigError {
         ConfigError {
@@ -235,10 +269,30 @@ impl From<ignore::Error> for ConfigError {
     }
 }
 
+impl From<regex::Error> for ConfigError {
+    fn from(err: regex::Error) -> ConfigError {
+        ConfigError {
+            file: None,
+            error: SpecificConfigError::Regex(err),
+        }
+    }
+}
+
+impl From<json::ParserError> for ConfigError {
+    fn from(err: json::ParserError) -> ConfigError {
+        ConfigError {
+            file: None,
+            error: SpecificConfigError::Json(err),
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum Error {
     Argument(ArgumentError),
     Config(ConfigError),
+    /// Failing while running, like losing the file watch or an editor connection
+    Io(io::Error),
 }
 
 impl error::Error for Error {
@@ -246,6 +300,7 @@ impl error::Error for Error {
         match *self {
             Error::Argument(ref err) => err.description(),
             Error::Config(ref err) => err.description(),
+            Error::Io(ref err) => err.description(),
         }
     }
 
@@ -253,6 +308,7 @@ impl error::Error for Error {
         match *self {
             Error::Argument(ref err) => Some(err),
             Error::Config(ref err) => Some(err),
+            Error::Io(ref err) => Some(err),
         }
     }
 }
@@ -262,6 +318,7 @@ impl fmt::Display for Error {
         match *self {
             Error::Argument(ref err) => err.fmt(f),
             Error::Config(ref err) => err.fmt(f),
+            Error::Io(ref err) => err.fmt(f),
         }
     }
 }
@@ -277,3 +334,9 @@ impl From<ConfigError> for Error {
         Error::Config(err)
     }
 }
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
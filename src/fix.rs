@@ -0,0 +1,332 @@
+use std::cmp;
+use std::str;
+
+use search;
+
+/// Lines of context around each change in a diff.
+pub const DIFF_CONTEXT: usize = 3;
+
+pub enum DiffLine {
+    Context(Vec<u8>),
+    Removed(Vec<u8>),
+    Added(Vec<u8>),
+}
+
+/// A unified diff hunk.
+///
+/// Lines include their terminator, if any.
+pub struct Hunk {
+    pub old_start: usize,
+    pub old_count: usize,
+    pub new_start: usize,
+    pub new_count: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+/// A decision on a proposed fix.
+pub enum Answer {
+    Accept,
+    Skip,
+    /// Accept this and all later fixes for the same lint
+    AcceptLint,
+    /// Stop without applying the current file's fixes
+    Quit,
+}
+
+pub static ANSWER_HELP: &'static str = "y - apply this fix
+n - skip this fix
+a - apply this and all later fixes for this lint
+q - quit, leaving this file unchanged
+";
+
+impl str::FromStr for Answer {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Answer, ()> {
+        match s {
+            "y" => Ok(Answer::Accept),
+            "n" => Ok(Answer::Skip),
+            "a" => Ok(Answer::AcceptLint),
+            "q" => Ok(Answer::Quit),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Violations whose replacements can be applied together.
+///
+/// `violations` must be ordered by position.  Violations without a replacement or that overlap an
+/// earlier replacement are skipped; a later run will pick the latter up.
+pub fn select<'v, 'l>(violations: &[&'v search::Violation<'l>]) -> Vec<&'v search::Violation<'l>> {
+    let mut selected = Vec::new();
+    let mut last = 0;
+    for violation in violations {
+        if violation.replacement.is_none() || violation.start < last {
+            continue;
+        }
+        last = violation.end;
+        selected.push(*violation);
+    }
+    selected
+}
+
+/// Replace each edit's span within `content[offset..end]`.
+fn splice(content: &[u8], offset: usize, end: usize, edits: &[&search::Violation]) -> Vec<u8> {
+    let mut fixed = Vec::with_capacity(end - offset);
+    let mut last = offset;
+    for edit in edits {
+        fixed.extend_from_slice(&content[last..edit.start]);
+        fixed.extend_from_slice(edit.replacement.as_ref().expect("Only replacements are selected"));
+        last = edit.end;
+    }
+    fixed.extend_from_slice(&content[last..end]);
+    fixed
+}
+
+/// Apply the replacements of `violations` to `content`.
+///
+/// See `select` for which violations are applied.  Returns `None` if nothing was replaced.
+pub fn apply(content: &[u8], violations: &[&search::Violation]) -> Option<Vec<u8>> {
+    let edits = select(violations);
+    if edits.is_empty() {
+        return None;
+    }
+    Some(splice(content, 0, content.len(), &edits))
+}
+
+fn split_lines(content: &[u8]) -> Vec<&[u8]> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    for (i, b) in content.iter().enumerate() {
+        if *b == b'\n' {
+            lines.push(&content[start..i + 1]);
+            start = i + 1;
+        }
+    }
+    if start < content.len() {
+        lines.push(&content[start..]);
+    }
+    lines
+}
+
+/// A run of whole lines touched by one or more edits.
+struct Change {
+    /// 0-based index of the first line
+    first: usize,
+    /// 0-based index one past the last line
+    last: usize,
+    new_lines: Vec<Vec<u8>>,
+}
+
+/// Unified diff hunks for applying the replacements of `violations` to `content`.
+///
+/// See `select` for which violations are applied.
+pub fn diff(content: &[u8], violations: &[&search::Violation], context: usize) -> Vec<Hunk> {
+    let edits = select(violations);
+    let old_lines = split_lines(content);
+    let mut line_starts = Vec::with_capacity(old_lines.len() + 1);
+    let mut offset = 0;
+    for line in &old_lines {
+        line_starts.push(offset);
+        offset += line.len();
+    }
+    line_starts.push(offset);
+    // Edits at the very end of the content belong to the last line.
+    let line_of = |offset: usize| -> usize {
+        let line = match line_starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        cmp::min(line, old_lines.len().saturating_sub(1))
+    };
+
+    let mut groups: Vec<(usize, usize, Vec<&search::Violation>)> = Vec::new();
+    for edit in edits {
+        let first = line_of(edit.start);
+        let last = line_of(cmp::max(edit.start, edit.end.saturating_sub(1))) + 1;
+        let last = cmp::min(cmp::max(last, first + 1), old_lines.len());
+        match groups.last_mut() {
+            Some(group) if first <= group.1 => {
+                group.1 = cmp::max(group.1, last);
+                group.2.push(edit);
+                continue;
+            }
+            _ => {}
+        }
+        groups.push((first, last, vec![edit]));
+    }
+    let changes: Vec<Change> = groups.into_iter()
+        .map(|(first, last, edits)| {
+            let fixed = splice(content, line_starts[first], line_starts[last], &edits);
+            Change {
+                first: first,
+                last: last,
+                new_lines: split_lines(&fixed).into_iter().map(|l| l.to_vec()).collect(),
+            }
+        })
+        .collect();
+
+    let mut hunks = Vec::new();
+    let mut delta: isize = 0;
+    let mut i = 0;
+    while i < changes.len() {
+        let mut j = i + 1;
+        while j < changes.len() && changes[j].first - changes[j - 1].last <= 2 * context {
+            j += 1;
+        }
+        let start = changes[i].first.saturating_sub(context);
+        let end = cmp::min(changes[j - 1].last + context, old_lines.len());
+        let mut lines = Vec::new();
+        let mut old_count = 0;
+        let mut new_count = 0;
+        let mut line = start;
+        for change in &changes[i..j] {
+            for old in &old_lines[line..change.first] {
+                lines.push(DiffLine::Context(old.to_vec()));
+            }
+            for old in &old_lines[change.first..change.last] {
+                lines.push(DiffLine::Removed(old.to_vec()));
+            }
+            for new in &change.new_lines {
+                lines.push(DiffLine::Added(new.clone()));
+            }
+            old_count += change.first - line + change.last - change.first;
+            new_count += change.first - line + change.new_lines.len();
+            line = change.last;
+        }
+        for old in &old_lines[line..end] {
+            lines.push(DiffLine::Context(old.to_vec()));
+        }
+        old_count += end - line;
+        new_count += end - line;
+
+        let old_start = if old_count == 0 { start } else { start + 1 };
+        let new_start = (start as isize + delta) as usize;
+        let new_start = if new_count == 0 { new_start } else { new_start + 1 };
+        hunks.push(Hunk {
+            old_start: old_start,
+            old_count: old_count,
+            new_start: new_start,
+            new_count: new_count,
+            lines: lines,
+        });
+        delta += new_count as isize - old_count as isize;
+        i = j;
+    }
+    hunks
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use lints;
+    use search;
+
+    fn lint() -> lints::Lint {
+        lints::TomlLintFactory::new("[test]\npattern = \"test\"\nmessage = \"Test\"\n")
+            .and_then(|f| f.build_lints())
+            .expect("Test lint is valid")
+            .remove(0)
+    }
+
+    /// A violation replacing the first `old` after `from` with `new`.
+    fn edit<'l>(lint: &'l lints::Lint,
+                content: &[u8],
+                from: usize,
+                old: &str,
+                new: &str)
+                -> search::Violation<'l> {
+        let start = from +
+                    content[from..]
+            .windows(old.len())
+            .position(|w| w == old.as_bytes())
+            .expect("Test edits are in the content");
+        let index = search::LineIndex::new(content);
+        let (line, column) = index.position(start);
+        search::Violation {
+            lint: lint,
+            line: line,
+            column: column,
+            start: start,
+            end: start + old.len(),
+            replacement: Some(new.as_bytes().to_vec()),
+        }
+    }
+
+    fn headers(hunks: &[Hunk]) -> Vec<String> {
+        hunks.iter()
+            .map(|h| format!("@@ -{},{} +{},{} @@", h.old_start, h.old_count, h.new_start, h.new_count))
+            .collect()
+    }
+
+    fn numbered(count: usize) -> Vec<u8> {
+        (1..count + 1).map(|i| format!("line{}\n", i)).collect::<String>().into_bytes()
+    }
+
+    #[test]
+    fn diff_separate_hunks_shift_new_start() {
+        let lint = lint();
+        let content = numbered(10);
+        let first = edit(&lint, &content, 0, "line2", "line2a\nline2b");
+        let second = edit(&lint, &content, 0, "line10", "line ten");
+        let hunks = diff(&content, &[&first, &second], 1);
+        assert_eq!(headers(&hunks), vec!["@@ -1,3 +1,4 @@", "@@ -9,2 +10,2 @@"]);
+    }
+
+    #[test]
+    fn diff_merges_changes_within_twice_context() {
+        let lint = lint();
+        let content = numbered(10);
+        let first = edit(&lint, &content, 0, "line2", "two");
+        let second = edit(&lint, &content, 0, "line5", "five");
+        let hunks = diff(&content, &[&first, &second], 1);
+        assert_eq!(headers(&hunks), vec!["@@ -1,6 +1,6 @@"]);
+
+        let third = edit(&lint, &content, 0, "line6", "six");
+        let hunks = diff(&content, &[&first, &third], 1);
+        assert_eq!(headers(&hunks), vec!["@@ -1,3 +1,3 @@", "@@ -5,3 +5,3 @@"]);
+    }
+
+    #[test]
+    fn diff_adjacent_edits_share_a_line() {
+        let lint = lint();
+        let content = b"a b c\nd\n".to_vec();
+        let first = edit(&lint, &content, 0, "a", "x");
+        let second = edit(&lint, &content, 1, "b", "y");
+        let hunks = diff(&content, &[&first, &second], 0);
+        assert_eq!(headers(&hunks), vec!["@@ -1,1 +1,1 @@"]);
+        match (&hunks[0].lines[0], &hunks[0].lines[1]) {
+            (&DiffLine::Removed(ref old), &DiffLine::Added(ref new)) => {
+                assert_eq!(old, b"a b c\n");
+                assert_eq!(new, b"x y c\n");
+            }
+            _ => panic!("Expected one removed and one added line"),
+        }
+    }
+
+    #[test]
+    fn diff_removed_line_counts_zero_new_lines() {
+        let lint = lint();
+        let content = b"a\nb\nc\n".to_vec();
+        let removed = edit(&lint, &content, 0, "b\n", "");
+        let hunks = diff(&content, &[&removed], 0);
+        assert_eq!(headers(&hunks), vec!["@@ -2,1 +1,0 @@"]);
+    }
+
+    #[test]
+    fn diff_last_line_without_newline() {
+        let lint = lint();
+        let content = b"a\nb".to_vec();
+        let last = edit(&lint, &content, 0, "b", "c");
+        let hunks = diff(&content, &[&last], 3);
+        assert_eq!(headers(&hunks), vec!["@@ -1,2 +1,2 @@"]);
+        match (&hunks[0].lines[1], &hunks[0].lines[2]) {
+            (&DiffLine::Removed(ref old), &DiffLine::Added(ref new)) => {
+                assert_eq!(old, b"b");
+                assert_eq!(new, b"c");
+            }
+            _ => panic!("Expected one removed and one added line"),
+        }
+    }
+}
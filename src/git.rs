@@ -0,0 +1,41 @@
+use std::ffi;
+use std::io;
+use std::path;
+use std::process;
+
+/// Output of a successful `git` command, otherwise its error message.
+pub fn output<S: AsRef<ffi::OsStr>>(args: &[S]) -> io::Result<Vec<u8>> {
+    let output = process::Command::new("git").args(args).output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::new(io::ErrorKind::Other, message));
+    }
+    Ok(output.stdout)
+}
+
+/// Files with staged changes that are still in the index, relative to the current directory.
+pub fn staged_paths() -> io::Result<Vec<path::PathBuf>> {
+    let names = output(&["diff", "--cached", "--name-only", "-z", "--diff-filter=ACMR", "--relative"])?;
+    Ok(paths(&names))
+}
+
+/// Files git doesn't know of and doesn't ignore, relative to the current directory.
+pub fn untracked_paths() -> io::Result<Vec<path::PathBuf>> {
+    let names = output(&["ls-files", "--others", "--exclude-standard", "-z"])?;
+    Ok(paths(&names))
+}
+
+/// Content of `path` as staged in the index.
+pub fn show_staged(path: &path::Path) -> io::Result<Vec<u8>> {
+    let mut object = ffi::OsString::from(":./");
+    object.push(path);
+    output(&[ffi::OsStr::new("show"), &object])
+}
+
+/// Paths from NUL separated `-z` output.
+fn paths(names: &[u8]) -> Vec<path::PathBuf> {
+    names.split(|b| *b == b'\0')
+        .filter(|name| !name.is_empty())
+        .map(|name| path::Path::new(&*String::from_utf8_lossy(name)).to_path_buf())
+        .collect()
+}
@@ -78,23 +78,61 @@ impl<'a> FileTypeDef<'a> {
 }
 
 arg_enum! {
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
     pub enum ErrorLevel {
-        Error,
-        Warning,
-        Info
+        Error = 3,
+        Warning = 2,
+        Info = 1
     }
 }
 
+impl ErrorLevel {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            ErrorLevel::Error => "error",
+            ErrorLevel::Warning => "warning",
+            ErrorLevel::Info => "info",
+        }
+    }
+}
+
+/// Name of the built-in lint for suppression comments that suppress nothing.
+pub static UNUSED_DISABLE: &'static str = "unused-disable";
+
+#[derive(Clone)]
 pub struct Lint {
+    /// The lint's table name
+    pub name: String,
     pub types: ignore::types::Types,
     pub severity: ErrorLevel,
     pub pattern: grep::Grep,
     pub message: Vec<u8>,
+    /// Template for fixing a match, supporting `$1` / `${name}` capture expansion
+    pub replace: Option<Vec<u8>>,
 }
 
 impl Lint {
-    fn new_from_table(lint: &toml::Table,
+    /// A lint relint reports itself, rather than one configured in a table.
+    pub fn builtin(name: &str, pattern: &str, severity: ErrorLevel, message: &str) -> Lint {
+        Lint {
+            name: name.to_string(),
+            types: ignore::types::TypesBuilder::new()
+                .build()
+                .expect("No types to fail on"),
+            severity: severity,
+            pattern: grep::GrepBuilder::new(pattern).build().expect("Built-in patterns are valid"),
+            message: message.as_bytes().to_vec(),
+            replace: None,
+        }
+    }
+
+    /// Whether the lint's `type` / `type-not` filters allow checking `path`.
+    pub fn applies_to(&self, path: &path::Path) -> bool {
+        !self.types.matched(path, false).is_ignore()
+    }
+
+    fn new_from_table(name: &str,
+                      lint: &toml::Table,
                       mut btypes: ignore::types::TypesBuilder)
                       -> Result<Lint, errors::FieldError> {
         if let Some(types) = lint.get("type") {
@@ -155,6 +193,11 @@ impl Lint {
         let message = force_get(lint, "message")?;
         let message = force_as_str(message, "message")?.as_bytes().to_vec();
 
+        let replace = match lint.get("replace") {
+            Some(replace) => Some(force_as_str(replace, "replace")?.as_bytes().to_vec()),
+            None => None,
+        };
+
         let pattern = force_get(lint, "pattern")?;
         let pattern = force_as_str(pattern, "pattern")?;
         let bpattern = grep::GrepBuilder::new(pattern);
@@ -162,11 +205,13 @@ impl Lint {
             .map_err(|e| errors::FieldError::new("severity", errors::SpecificFieldError::Grep(e)))?;
 
         Ok(Lint {
+            name: name.to_string(),
             types:
                 btypes.build()
                 .map_err(|e| errors::FieldError::new("...", errors::SpecificFieldError::Ignore(e)))?,
             severity: severity,
             message: message,
+            replace: replace,
             pattern: pattern,
         })
     }
@@ -279,7 +324,7 @@ impl<'a> TomlLintFactory<'a> {
                                             actual: settings.type_str().to_string(),
                                         })
             })?;
-        let lint = Lint::new_from_table(settings, btypes)?;
+        let lint = Lint::new_from_table(check_name, settings, btypes)?;
         Ok(lint)
     }
 
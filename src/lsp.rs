@@ -0,0 +1,544 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path;
+use std::str;
+
+use rustc_serialize::json::{Json, ToJson};
+
+use args;
+use errors;
+use lints;
+use printer::json_object;
+use search;
+
+// JSON-RPC and LSP error codes
+static PARSE_ERROR: i64 = -32700;
+static INVALID_REQUEST: i64 = -32600;
+static METHOD_NOT_FOUND: i64 = -32601;
+static SERVER_NOT_INITIALIZED: i64 = -32002;
+
+/// `TextDocumentSyncKind.Full`: clients send the whole document on every change
+static SYNC_FULL: u64 = 1;
+/// `MessageType.Error`
+static MESSAGE_ERROR: u64 = 1;
+
+static WATCHER_ID: &'static str = "relint-config";
+
+/// An open document, as last sent by the client.
+struct Document {
+    /// `None` for documents that aren't files, which don't get linted
+    path: Option<path::PathBuf>,
+    version: Option<i64>,
+    text: String,
+}
+
+/// A Language Server Protocol server, linting open documents as they are edited.
+///
+/// Each document is linted with the nearest config file to its workspace folder, unless one
+/// was given on the command line.
+pub struct Server<R, W> {
+    reader: R,
+    writer: W,
+    /// Config file for all documents, overriding the lookup per workspace folder
+    lint_path: Option<path::PathBuf>,
+    folders: Vec<path::PathBuf>,
+    documents: BTreeMap<String, Document>,
+    /// Searcher per config file, or `None` if it failed to load
+    searchers: BTreeMap<path::PathBuf, Option<search::Searcher>>,
+    initialized: bool,
+    shutdown: bool,
+    /// Whether the client lets us ask it to watch config files
+    watch_registration: bool,
+    next_id: u64,
+}
+
+impl<R: BufRead, W: Write> Server<R, W> {
+    pub fn new(reader: R, writer: W, lint_path: Option<path::PathBuf>) -> Server<R, W> {
+        Server {
+            reader: reader,
+            writer: writer,
+            lint_path: lint_path,
+            folders: Vec::new(),
+            documents: BTreeMap::new(),
+            searchers: BTreeMap::new(),
+            initialized: false,
+            shutdown: false,
+            watch_registration: false,
+            next_id: 0,
+        }
+    }
+
+    /// Serve until the client exits, returning whether it shut the server down first.
+    pub fn run(mut self) -> io::Result<bool> {
+        loop {
+            let body = match self.read_message()? {
+                Some(body) => body,
+                None => return Ok(false),
+            };
+            let message = match str::from_utf8(&body) {
+                Ok(body) => Json::from_str(body).map_err(|e| e.to_string()),
+                Err(err) => Err(err.to_string()),
+            };
+            let message = match message {
+                Ok(message) => message,
+                Err(err) => {
+                    self.respond_error(Json::Null, PARSE_ERROR, &err)?;
+                    continue;
+                }
+            };
+            let method = message.find("method").and_then(|m| m.as_string()).map(|m| m.to_string());
+            let params = message.find("params").cloned().unwrap_or(Json::Null);
+            match (method, message.find("id").cloned()) {
+                (Some(ref method), _) if method == "exit" => return Ok(self.shutdown),
+                (Some(method), Some(id)) => self.handle_request(id, &method, &params)?,
+                (Some(method), None) => self.handle_notification(&method, &params)?,
+                // A response to one of our requests
+                (None, _) => {}
+            }
+        }
+    }
+
+    fn handle_request(&mut self, id: Json, method: &str, params: &Json) -> io::Result<()> {
+        if method == "initialize" {
+            self.initialize(params);
+            return self.respond(id, capabilities());
+        }
+        if !self.initialized {
+            return self.respond_error(id, SERVER_NOT_INITIALIZED, "Server is not initialized");
+        }
+        if self.shutdown {
+            return self.respond_error(id, INVALID_REQUEST, "Server is shutting down");
+        }
+        match method {
+            "shutdown" => {
+                self.shutdown = true;
+                self.respond(id, Json::Null)
+            }
+            "textDocument/codeAction" => {
+                let actions = self.code_actions(params)?;
+                self.respond(id, Json::Array(actions))
+            }
+            _ => self.respond_error(id, METHOD_NOT_FOUND, &format!("Unknown method {}", method)),
+        }
+    }
+
+    fn handle_notification(&mut self, method: &str, params: &Json) -> io::Result<()> {
+        match method {
+            "initialized" => self.register_watcher(),
+            "textDocument/didOpen" => {
+                let document = params.find("textDocument");
+                let uri = match document.and_then(|d| d.find("uri")).and_then(|u| u.as_string()) {
+                    Some(uri) => uri.to_string(),
+                    None => return Ok(()),
+                };
+                let text = document.and_then(|d| d.find("text"))
+                    .and_then(|t| t.as_string())
+                    .unwrap_or("");
+                self.documents.insert(uri.clone(),
+                                      Document {
+                                          path: uri_to_path(&uri),
+                                          version: document.and_then(|d| d.find("version"))
+                                              .and_then(|v| v.as_i64()),
+                                          text: text.to_string(),
+                                      });
+                self.publish(&uri)
+            }
+            "textDocument/didChange" => {
+                let uri = match params.find_path(&["textDocument", "uri"])
+                    .and_then(|u| u.as_string()) {
+                    Some(uri) => uri.to_string(),
+                    None => return Ok(()),
+                };
+                // Changes are always whole documents, as that's the sync we asked for
+                let text = params.find("contentChanges")
+                    .and_then(|c| c.as_array())
+                    .and_then(|c| c.last())
+                    .and_then(|c| c.find("text"))
+                    .and_then(|t| t.as_string());
+                let version = params.find_path(&["textDocument", "version"])
+                    .and_then(|v| v.as_i64());
+                if let (Some(text), Some(document)) = (text, self.documents.get_mut(&uri)) {
+                    document.text = text.to_string();
+                    document.version = version;
+                }
+                self.publish(&uri)
+            }
+            "textDocument/didClose" => {
+                let uri = match params.find_path(&["textDocument", "uri"])
+                    .and_then(|u| u.as_string()) {
+                    Some(uri) => uri.to_string(),
+                    None => return Ok(()),
+                };
+                self.documents.remove(&uri);
+                self.notify("textDocument/publishDiagnostics",
+                            json_object(vec![("uri", uri.to_json()),
+                                             ("diagnostics", Json::Array(Vec::new()))]))
+            }
+            "workspace/didChangeWorkspaceFolders" => {
+                let removed = params.find_path(&["event", "removed"]).map(folder_paths);
+                for removed in removed.unwrap_or_else(Vec::new) {
+                    self.folders.retain(|folder| *folder != removed);
+                }
+                let added = params.find_path(&["event", "added"]).map(folder_paths);
+                self.folders.extend(added.unwrap_or_else(Vec::new));
+                self.publish_all()
+            }
+            "workspace/didChangeWatchedFiles" => {
+                self.searchers.clear();
+                self.publish_all()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn initialize(&mut self, params: &Json) {
+        self.initialized = true;
+        self.watch_registration = params.find_path(&["capabilities",
+                                                     "workspace",
+                                                     "didChangeWatchedFiles",
+                                                     "dynamicRegistration"])
+            .and_then(|r| r.as_boolean())
+            .unwrap_or(false);
+        self.folders = match params.find("workspaceFolders") {
+            Some(folders) if folders.is_array() => folder_paths(folders),
+            _ => {
+                let root_uri = params.find("rootUri")
+                    .and_then(|u| u.as_string())
+                    .and_then(uri_to_path);
+                let root_path = params.find("rootPath")
+                    .and_then(|p| p.as_string())
+                    .map(|p| path::Path::new(p).to_path_buf());
+                root_uri.or(root_path).into_iter().collect()
+            }
+        };
+    }
+
+    /// Ask the client to tell us when config files change.
+    fn register_watcher(&mut self) -> io::Result<()> {
+        if !self.watch_registration {
+            return Ok(());
+        }
+        let name = self.lint_path
+            .as_ref()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_else(|| args::DEFAULT_CONFIG_FILE.to_string());
+        let watcher = json_object(vec![("globPattern", format!("**/{}", name).to_json())]);
+        let registration =
+            json_object(vec![("id", WATCHER_ID.to_json()),
+                             ("method", "workspace/didChangeWatchedFiles".to_json()),
+                             ("registerOptions",
+                              json_object(vec![("watchers", Json::Array(vec![watcher]))]))]);
+        self.request("client/registerCapability",
+                     json_object(vec![("registrations", Json::Array(vec![registration]))]))
+    }
+
+    fn publish_all(&mut self) -> io::Result<()> {
+        let uris: Vec<String> = self.documents.keys().cloned().collect();
+        for uri in uris {
+            self.publish(&uri)?;
+        }
+        Ok(())
+    }
+
+    fn publish(&mut self, uri: &str) -> io::Result<()> {
+        let diagnostics = self.lint(uri, |content, index, violation| {
+                Some(diagnostic(content, index, violation))
+            })?;
+        let mut params = vec![("uri", uri.to_json()), ("diagnostics", Json::Array(diagnostics))];
+        if let Some(version) = self.documents.get(uri).and_then(|d| d.version) {
+            params.push(("version", version.to_json()));
+        }
+        self.notify("textDocument/publishDiagnostics", json_object(params))
+    }
+
+    /// A quick fix for each finding with a replacement within the requested range.
+    fn code_actions(&mut self, params: &Json) -> io::Result<Vec<Json>> {
+        let uri = match params.find_path(&["textDocument", "uri"]).and_then(|u| u.as_string()) {
+            Some(uri) => uri.to_string(),
+            None => return Ok(Vec::new()),
+        };
+        let range = params.find("range");
+        let (first, last) = match (range.and_then(|r| r.find("start")).and_then(parse_position),
+                                   range.and_then(|r| r.find("end")).and_then(parse_position)) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Ok(Vec::new()),
+        };
+        self.lint(&uri, |content, index, violation| {
+            let replacement = match violation.replacement {
+                Some(ref replacement) => String::from_utf8_lossy(replacement).into_owned(),
+                None => return None,
+            };
+            let start = position(content, index, violation.start);
+            let end = position(content, index, violation.end);
+            if end < first || last < start {
+                return None;
+            }
+            let edit = json_object(vec![("range", range_json(start, end)),
+                                        ("newText", replacement.to_json())]);
+            let mut changes = BTreeMap::new();
+            changes.insert(uri.clone(), Json::Array(vec![edit]));
+            Some(json_object(vec![("title", format!("Replace with `{}`", replacement).to_json()),
+                                  ("kind", "quickfix".to_json()),
+                                  ("diagnostics",
+                                   Json::Array(vec![diagnostic(content, index, violation)])),
+                                  ("edit", json_object(vec![("changes", Json::Object(changes))]))]))
+        })
+    }
+
+    /// Findings in the open document `uri`, as converted by `convert`.
+    fn lint<T, F>(&mut self, uri: &str, mut convert: F) -> io::Result<Vec<T>>
+        where F: FnMut(&[u8], &search::LineIndex, &search::Violation) -> Option<T>
+    {
+        let path = match self.documents.get(uri).and_then(|d| d.path.clone()) {
+            Some(path) => path,
+            None => return Ok(Vec::new()),
+        };
+        let lint_path = match self.lint_path_for(&path) {
+            Some(lint_path) => lint_path,
+            None => return Ok(Vec::new()),
+        };
+        self.load(&lint_path)?;
+
+        let searcher = match self.searchers.get(&lint_path) {
+            Some(&Some(ref searcher)) => searcher,
+            _ => return Ok(Vec::new()),
+        };
+        // Types are matched the same as when walking from the workspace folder
+        let relative = match self.folder_for(&path) {
+            Some(folder) => path.strip_prefix(folder).unwrap_or(&path),
+            None => &path,
+        };
+        if !searcher.applies_to(relative) {
+            return Ok(Vec::new());
+        }
+        let content = self.documents[uri].text.as_bytes();
+        let index = search::LineIndex::new(content);
+        Ok(searcher.search(relative, content)
+            .iter()
+            .filter_map(|violation| convert(content, &index, violation))
+            .collect())
+    }
+
+    /// Load the config at `lint_path` if not already, telling the user if it's invalid.
+    fn load(&mut self, lint_path: &path::Path) -> io::Result<()> {
+        if self.searchers.contains_key(lint_path) {
+            return Ok(());
+        }
+        let searcher = lints::TomlLintFactory::new_from_path(lint_path)
+            .and_then(|factory| factory.build_lints())
+            .and_then(|lints| {
+                search::Searcher::new(lints, lints::ErrorLevel::Info)
+                    .map_err(|e| e.add_path(Some(lint_path)))
+            });
+        match searcher {
+            Ok(searcher) => {
+                self.searchers.insert(lint_path.to_path_buf(), Some(searcher));
+                Ok(())
+            }
+            Err(err) => {
+                self.searchers.insert(lint_path.to_path_buf(), None);
+                self.show_error(&err)
+            }
+        }
+    }
+
+    /// The innermost workspace folder containing `path`.
+    fn folder_for(&self, path: &path::Path) -> Option<&path::Path> {
+        self.folders
+            .iter()
+            .filter(|folder| path.starts_with(folder))
+            .max_by_key(|folder| folder.components().count())
+            .map(|folder| folder.as_path())
+    }
+
+    fn lint_path_for(&self, path: &path::Path) -> Option<path::PathBuf> {
+        if let Some(ref lint_path) = self.lint_path {
+            return Some(lint_path.clone());
+        }
+        let dir = match self.folder_for(path) {
+            Some(folder) => folder,
+            None => {
+                match path.parent() {
+                    Some(dir) => dir,
+                    None => return None,
+                }
+            }
+        };
+        args::find_project_file(dir, args::DEFAULT_CONFIG_FILE)
+    }
+
+    fn show_error(&mut self, err: &errors::ConfigError) -> io::Result<()> {
+        // Logging goes to stderr, so it can't interleave with messages on stdout
+        error!("{}", err);
+        self.notify("window/showMessage",
+                    json_object(vec![("type", MESSAGE_ERROR.to_json()),
+                                     ("message", err.to_string().to_json())]))
+    }
+
+    /// Body of the next message, or `None` once the client has gone.
+    fn read_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if self.reader.read_line(&mut header)? == 0 {
+                return Ok(None);
+            }
+            let header = header.trim_right();
+            if header.is_empty() {
+                if length.is_some() {
+                    break;
+                }
+                continue;
+            }
+            let mut parts = header.splitn(2, ':');
+            let name = parts.next().unwrap_or("").trim().to_lowercase();
+            if name == "content-length" {
+                length = parts.next().and_then(|value| value.trim().parse::<usize>().ok());
+            }
+        }
+        let mut body = vec![0; length.expect("Checked above")];
+        self.reader.read_exact(&mut body)?;
+        Ok(Some(body))
+    }
+
+    fn send(&mut self, mut fields: Vec<(&str, Json)>) -> io::Result<()> {
+        fields.insert(0, ("jsonrpc", "2.0".to_json()));
+        let body = json_object(fields).to_string();
+        write!(self.writer, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.writer.flush()
+    }
+
+    fn respond(&mut self, id: Json, result: Json) -> io::Result<()> {
+        self.send(vec![("id", id), ("result", result)])
+    }
+
+    fn respond_error(&mut self, id: Json, code: i64, message: &str) -> io::Result<()> {
+        let error = json_object(vec![("code", code.to_json()), ("message", message.to_json())]);
+        self.send(vec![("id", id), ("error", error)])
+    }
+
+    fn notify(&mut self, method: &str, params: Json) -> io::Result<()> {
+        self.send(vec![("method", method.to_json()), ("params", params)])
+    }
+
+    fn request(&mut self, method: &str, params: Json) -> io::Result<()> {
+        self.next_id += 1;
+        let id = format!("relint-{}", self.next_id);
+        self.send(vec![("id", id.to_json()), ("method", method.to_json()), ("params", params)])
+    }
+}
+
+fn capabilities() -> Json {
+    let sync = json_object(vec![("openClose", true.to_json()), ("change", SYNC_FULL.to_json())]);
+    let folders = json_object(vec![("supported", true.to_json()),
+                                   ("changeNotifications", true.to_json())]);
+    let capabilities =
+        json_object(vec![("textDocumentSync", sync),
+                         ("codeActionProvider",
+                          json_object(vec![("codeActionKinds",
+                                            vec!["quickfix".to_string()].to_json())])),
+                         ("workspace", json_object(vec![("workspaceFolders", folders)]))]);
+    json_object(vec![("capabilities", capabilities),
+                     ("serverInfo",
+                      json_object(vec![("name", "relint".to_json()),
+                                       ("version", crate_version!().to_json())]))])
+}
+
+fn diagnostic(content: &[u8], index: &search::LineIndex, violation: &search::Violation) -> Json {
+    let severity: u64 = match violation.lint.severity {
+        lints::ErrorLevel::Error => 1,
+        lints::ErrorLevel::Warning => 2,
+        lints::ErrorLevel::Info => 3,
+    };
+    let message = String::from_utf8_lossy(&violation.lint.message).into_owned();
+    json_object(vec![("range",
+                      range_json(position(content, index, violation.start),
+                                 position(content, index, violation.end))),
+                     ("severity", severity.to_json()),
+                     ("code", violation.lint.name.to_json()),
+                     ("source", "relint".to_json()),
+                     ("message", message.to_json())])
+}
+
+/// 0-based line and character of `offset`, with characters counted in UTF-16 code units as
+/// LSP expects.
+fn position(content: &[u8], index: &search::LineIndex, offset: usize) -> (usize, usize) {
+    let (line, column) = index.position(offset);
+    let line_start = offset + 1 - column;
+    let character = String::from_utf8_lossy(&content[line_start..offset]).encode_utf16().count();
+    (line - 1, character)
+}
+
+fn parse_position(position: &Json) -> Option<(usize, usize)> {
+    let line = position.find("line").and_then(|l| l.as_u64());
+    let character = position.find("character").and_then(|c| c.as_u64());
+    match (line, character) {
+        (Some(line), Some(character)) => Some((line as usize, character as usize)),
+        _ => None,
+    }
+}
+
+fn range_json(start: (usize, usize), end: (usize, usize)) -> Json {
+    let position = |(line, character): (usize, usize)| {
+        json_object(vec![("line", line.to_json()), ("character", character.to_json())])
+    };
+    json_object(vec![("start", position(start)), ("end", position(end))])
+}
+
+/// Paths of an array of `WorkspaceFolder`s.
+fn folder_paths(folders: &Json) -> Vec<path::PathBuf> {
+    folders.as_array()
+        .map(|folders| {
+            folders.iter()
+                .filter_map(|f| f.find("uri").and_then(|u| u.as_string()).and_then(uri_to_path))
+                .collect()
+        })
+        .unwrap_or_else(Vec::new)
+}
+
+/// Path of a `file:` URI, or `None` for any other scheme.
+fn uri_to_path(uri: &str) -> Option<path::PathBuf> {
+    if !uri.starts_with("file://") {
+        return None;
+    }
+    // Skip the authority, which is normally empty
+    let encoded = &uri["file://".len()..];
+    let encoded = match encoded.find('/') {
+        Some(i) => &encoded.as_bytes()[i..],
+        None => return None,
+    };
+
+    let mut decoded = Vec::with_capacity(encoded.len());
+    let mut i = 0;
+    while i < encoded.len() {
+        let escaped = if encoded[i] == b'%' && i + 2 < encoded.len() {
+            str::from_utf8(&encoded[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+        match escaped {
+            Some(b) => {
+                decoded.push(b);
+                i += 3;
+            }
+            None => {
+                decoded.push(encoded[i]);
+                i += 1;
+            }
+        }
+    }
+    let mut decoded = match String::from_utf8(decoded) {
+        Ok(decoded) => decoded,
+        Err(_) => return None,
+    };
+    // `/C:/dir` on Windows
+    if cfg!(windows) && decoded.len() > 2 && decoded.as_bytes()[2] == b':' {
+        decoded.remove(0);
+    }
+    Some(path::Path::new(&decoded).to_path_buf())
+}
@@ -5,9 +5,11 @@ extern crate kernel32;
 #[cfg(windows)]
 extern crate winapi;
 extern crate grep;
+extern crate regex;
 extern crate ignore;
 extern crate toml;
 extern crate libc;
+extern crate rustc_serialize;
 #[macro_use(slog_error, slog_log)]
 extern crate slog;
 extern crate slog_term;
@@ -19,9 +21,28 @@ mod args;
 mod ripgrep_stolen;
 mod lints;
 mod printer;
+mod search;
+mod fix;
+mod suppress;
+mod baseline;
+mod changes;
+mod git;
+mod watch;
+mod lsp;
 
+use std::collections::BTreeMap;
+use std::ffi;
+use std::fs;
+use std::path;
 use std::io;
+use std::io::Write;
+use std::sync::Arc;
+use std::sync::mpsc;
+use std::thread;
+use std::time;
 use errors::Error;
+use ripgrep_stolen::atty;
+use ripgrep_stolen::pathutil;
 use slog::DrainExt;
 
 enum ActionStatus {
@@ -68,72 +89,700 @@ fn get_or_log_dir_entry(entry: Result<ignore::DirEntry, ignore::Error>)
     }
 }
 
-fn is_file_supported(dent: &ignore::DirEntry, lints: &[lints::Lint]) -> bool {
-    lints.iter()
-        .any(|lint| lint.types.is_empty() || lint.types.matched(dent.path(), false).is_whitelist())
+/// The file to lint for `dent`, with stdin standing in for `stdin_filename` if given.
+fn entry_source(dent: ignore::DirEntry, stdin_filename: &Option<path::PathBuf>) -> search::Source {
+    match *stdin_filename {
+        Some(ref path) if dent.is_stdin() => search::Source::Stdin(path.clone()),
+        _ => search::Source::Entry(dent),
+    }
+}
+
+/// Content of and violations within `source` or `None` if no lint applies to it.
+fn search_entry<'s>(source: &search::Source,
+                    searcher: &'s search::Searcher)
+                    -> Option<(Vec<u8>, Vec<search::Violation<'s>>)> {
+    if !searcher.applies_to(source.path()) {
+        return None;
+    }
+    match source.read() {
+        Ok(content) => {
+            let violations = searcher.search(source.path(), &content);
+            Some((content, violations))
+        }
+        Err(err) => {
+            error!("{}: {}", source.path().display(), err);
+            None
+        }
+    }
+}
+
+/// Output for a single file, buffered so files can be linted in parallel.
+#[derive(Clone)]
+struct FileReport {
+    path: path::PathBuf,
+    printer: printer::IoPrinter<Vec<u8>>,
+    /// Whether the file was listed (`--files`) or had errors
+    matched: bool,
+    summary: search::Summary,
+}
+
+/// Lint `source`, returning whether it was listed (`--files`) or had errors.
+fn lint_entry(printer: &mut printer::IoPrinter<Vec<u8>>,
+              summary: &mut search::Summary,
+              source: &search::Source,
+              searcher: &search::Searcher,
+              min_severity: lints::ErrorLevel,
+              output: args::SearchOutput)
+              -> bool {
+    if !searcher.applies_to(source.path()) {
+        summary.skipped += 1;
+        return false;
+    }
+    if output == args::SearchOutput::None {
+        summary.files += 1;
+        printer.path(source.path());
+        return true;
+    }
+    let content = match source.read() {
+        Ok(content) => content,
+        Err(err) => {
+            error!("{}: {}", source.path().display(), err);
+            return false;
+        }
+    };
+    summary.files += 1;
+    summary.bytes += content.len();
+    let violations = searcher.search(source.path(), &content);
+    let has_errors = violations.iter().any(|v| min_severity <= v.lint.severity);
+    for violation in &violations {
+        summary.add_violation(violation);
+    }
+    match output {
+        args::SearchOutput::Message => {
+            printer.begin_file(source.path(), &searcher.applicable(source.path()));
+            for violation in &violations {
+                printer.violation(source.path(), &content, violation);
+            }
+            printer.end_file();
+        }
+        args::SearchOutput::File { matched } => {
+            if has_errors == matched {
+                printer.path(source.path());
+            }
+        }
+        args::SearchOutput::Count => {
+            if !violations.is_empty() {
+                printer.count(source.path(), violations.len());
+            }
+        }
+        args::SearchOutput::CountByLint => {}
+        args::SearchOutput::None => unreachable!("Listed above"),
+    }
+    has_errors
+}
+
+fn report_entry(template: &printer::IoPrinter<Vec<u8>>,
+                source: search::Source,
+                searcher: &search::Searcher,
+                min_severity: lints::ErrorLevel,
+                output: args::SearchOutput)
+                -> FileReport {
+    let mut printer = template.clone();
+    let mut summary = search::Summary::default();
+    summary.walked = 1;
+    let matched = lint_entry(&mut printer, &mut summary, &source, searcher, min_severity, output);
+    FileReport {
+        path: source.path().to_path_buf(),
+        printer: printer,
+        matched: matched,
+        summary: summary,
+    }
+}
+
+fn print_reports<W, I>(printer: &mut printer::IoPrinter<W>,
+                       searcher: &search::Searcher,
+                       reports: I,
+                       output: args::SearchOutput,
+                       sort: bool,
+                       started: Option<time::Instant>)
+                       -> ActionStatus
+    where W: io::Write,
+          I: Iterator<Item = FileReport>
+{
+    let mut any_matched = false;
+    let mut summary = search::Summary::default();
+    if output == args::SearchOutput::Message {
+        printer.begin();
+    }
+    if sort {
+        let mut reports: Vec<FileReport> = reports.collect();
+        reports.sort_by(|a, b| a.path.cmp(&b.path));
+        for report in reports {
+            any_matched |= report.matched;
+            summary.merge(&report.summary);
+            printer.buffered(report.printer);
+        }
+    } else {
+        for report in reports {
+            any_matched |= report.matched;
+            summary.merge(&report.summary);
+            printer.buffered(report.printer);
+        }
+    }
+    let elapsed = started.map(|started| started.elapsed());
+    match output {
+        args::SearchOutput::Message => printer.finish(searcher, &summary, elapsed),
+        args::SearchOutput::CountByLint => {
+            for lint in searcher.reported() {
+                if let Some(count) = summary.lints.get(&lint.name) {
+                    printer.lint_count(lint, *count);
+                }
+            }
+        }
+        _ => {}
+    }
+    if let Some(elapsed) = elapsed {
+        printer.stats(&searcher.reported(), &summary, elapsed);
+    }
+
+    match (output, any_matched) {
+        (args::SearchOutput::None, true) |
+        (args::SearchOutput::Message, false) |
+        (args::SearchOutput::File { .. }, false) |
+        (args::SearchOutput::Count, false) |
+        (args::SearchOutput::CountByLint, false) => ActionStatus::Success,
+        _ => ActionStatus::Failure,
+    }
 }
 
-fn run_file_one_thread<W: io::Write>(printer: &mut printer::IoPrinter<W>,
-                                     walker: ignore::Walk,
-                                     lints: &[lints::Lint])
+fn run_search_one_thread<W, I>(printer: &mut printer::IoPrinter<W>,
+                               sources: I,
+                               searcher: &search::Searcher,
+                               min_severity: lints::ErrorLevel,
+                               output: args::SearchOutput,
+                               sort: bool,
+                               started: Option<time::Instant>)
+                               -> Result<ActionStatus, Error>
+    where W: io::Write,
+          I: Iterator<Item = search::Source>
+{
+    let template = printer.buffer();
+    let reports = sources.map(|source| report_entry(&template, source, searcher, min_severity, output));
+    Ok(print_reports(printer, searcher, reports, output, sort, started))
+}
+
+fn run_search_parallel<W: io::Write>(printer: &mut printer::IoPrinter<W>,
+                                     walker: ignore::WalkParallel,
+                                     searcher: Arc<search::Searcher>,
+                                     min_severity: lints::ErrorLevel,
+                                     output: args::SearchOutput,
+                                     sort: bool,
+                                     started: Option<time::Instant>)
                                      -> Result<ActionStatus, Error> {
-    let mut status = ActionStatus::Failure;
-    for dent in walker.filter_map(get_or_log_dir_entry)
-        .filter(|dent| is_file_supported(dent, lints)) {
-        status = ActionStatus::Success;
-        printer.path(dent.path());
+    let template = printer.buffer();
+    let (tx, rx) = mpsc::channel();
+    let walker_searcher = searcher.clone();
+    let walking = thread::spawn(move || {
+        walker.run(|| {
+            let tx = tx.clone();
+            let searcher = walker_searcher.clone();
+            let template = template.clone();
+            Box::new(move |entry| {
+                let dent = match get_or_log_dir_entry(entry) {
+                    Some(dent) => dent,
+                    None => return ignore::WalkState::Continue,
+                };
+                if !searcher.is_changed(dent.path()) {
+                    return ignore::WalkState::Continue;
+                }
+                let report = report_entry(&template,
+                                          search::Source::Entry(dent),
+                                          &searcher,
+                                          min_severity,
+                                          output);
+                if tx.send(report).is_err() {
+                    return ignore::WalkState::Quit;
+                }
+                ignore::WalkState::Continue
+            })
+        });
+    });
+    let status = print_reports(printer, &searcher, rx.into_iter(), output, sort, started);
+    walking.join().expect("Walker thread panicked");
+    Ok(status)
+}
+
+/// Replace `path` with `content`, without leaving it half-written on failure.
+///
+/// Symbolic links are followed, so the file they point to is replaced rather than the link, and
+/// the replacement keeps the file's permissions and owner.
+fn write_file(path: &path::Path, content: &[u8]) -> io::Result<()> {
+    let path = fs::canonicalize(path)?;
+    let metadata = fs::metadata(&path)?;
+    let mut tmp_name = ffi::OsString::from(".");
+    tmp_name.push(path.file_name().expect("Walked files always have a name"));
+    tmp_name.push(".relint");
+    let tmp_path = path.with_file_name(tmp_name);
+    let result = fs::File::create(&tmp_path)
+        .and_then(|mut f| {
+            f.write_all(content)?;
+            f.set_permissions(metadata.permissions())
+        })
+        .and_then(|_| set_owner(&tmp_path, &metadata))
+        .and_then(|_| fs::rename(&tmp_path, &path));
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp_path);
+    }
+    result
+}
+
+#[cfg(unix)]
+fn set_owner(path: &path::Path, metadata: &fs::Metadata) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::fs::MetadataExt;
+
+    let c_path = ffi::CString::new(path.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    if unsafe { libc::chown(c_path.as_ptr(), metadata.uid(), metadata.gid()) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &path::Path, _metadata: &fs::Metadata) -> io::Result<()> {
+    Ok(())
+}
+
+fn ask_fix<W: io::Write>(printer: &mut printer::IoPrinter<W>) -> fix::Answer {
+    let stdin = io::stdin();
+    loop {
+        printer.prompt("Apply this fix [y,n,a,q,?]? ");
+        let mut answer = String::new();
+        match stdin.read_line(&mut answer) {
+            Ok(0) | Err(_) => return fix::Answer::Quit,
+            Ok(_) => {}
+        }
+        match answer.trim().parse() {
+            Ok(answer) => return answer,
+            Err(_) => printer.prompt(fix::ANSWER_HELP),
+        }
+    }
+}
+
+/// Fixes the user accepted or `None` if they quit.
+fn review_fixes<'v, 'l, W: io::Write>(printer: &mut printer::IoPrinter<W>,
+                                      path: &path::Path,
+                                      content: &[u8],
+                                      violations: &[&'v search::Violation<'l>],
+                                      accepted_lints: &mut Vec<&'l lints::Lint>)
+                                      -> Option<Vec<&'v search::Violation<'l>>> {
+    let mut accepted = Vec::new();
+    for violation in violations {
+        if accepted_lints.iter().any(|lint| lint.name == violation.lint.name) {
+            accepted.push(*violation);
+            continue;
+        }
+        printer.diff(path, &fix::diff(content, &[*violation], fix::DIFF_CONTEXT));
+        match ask_fix(printer) {
+            fix::Answer::Accept => accepted.push(*violation),
+            fix::Answer::Skip => {}
+            fix::Answer::AcceptLint => {
+                accepted.push(*violation);
+                accepted_lints.push(violation.lint);
+            }
+            fix::Answer::Quit => return None,
+        }
+    }
+    Some(accepted)
+}
+
+fn run_fix_one_thread<W, I>(printer: &mut printer::IoPrinter<W>,
+                            sources: I,
+                            searcher: &search::Searcher,
+                            mode: &args::FixMode)
+                            -> Result<ActionStatus, Error>
+    where W: io::Write,
+          I: Iterator<Item = search::Source>
+{
+    let mut status = ActionStatus::Success;
+    let mut accepted_lints = Vec::new();
+    for source in sources {
+        let (content, violations) = match search_entry(&source, searcher) {
+            Some(entry) => entry,
+            None => continue,
+        };
+        let violations: Vec<&search::Violation> = violations.iter().collect();
+        let violations = fix::select(&violations);
+        if violations.is_empty() {
+            continue;
+        }
+        if *mode == args::FixMode::Diff {
+            printer.diff(source.path(), &fix::diff(&content, &violations, fix::DIFF_CONTEXT));
+            continue;
+        }
+        if source.is_stdin() {
+            error!("{}: Cannot fix in place", source.path().display());
+            status = ActionStatus::Failure;
+            continue;
+        }
+        let violations = if *mode == args::FixMode::Interactive {
+            match review_fixes(printer, source.path(), &content, &violations, &mut accepted_lints) {
+                Some(accepted) => accepted,
+                None => break,
+            }
+        } else {
+            violations
+        };
+        let fixed = match fix::apply(&content, &violations) {
+            Some(fixed) => fixed,
+            None => continue,
+        };
+        match write_file(source.path(), &fixed) {
+            Ok(()) => printer.path(source.path()),
+            Err(err) => {
+                error!("{}: {}", source.path().display(), err);
+                status = ActionStatus::Failure;
+            }
+        }
     }
     Ok(status)
 }
 
+fn run_write_baseline<I>(sources: I,
+                         searcher: &search::Searcher,
+                         path: &path::Path)
+                         -> Result<ActionStatus, Error>
+    where I: Iterator<Item = search::Source>
+{
+    let mut baseline = baseline::Baseline::new(path);
+    for source in sources {
+        if let Some((content, violations)) = search_entry(&source, searcher) {
+            for violation in &violations {
+                baseline.add(source.path(), &content, violation);
+            }
+        }
+    }
+    baseline.save(path)?;
+    Ok(ActionStatus::Success)
+}
+
+/// Whether `changed` names the file at `path`, however either is spelled.
+fn is_same_file(changed: &path::Path, path: &path::Path) -> bool {
+    fn parent(path: &path::Path) -> Option<path::PathBuf> {
+        match path.parent() {
+            Some(dir) if dir != path::Path::new("") => dir.canonicalize().ok(),
+            _ => path::Path::new(".").canonicalize().ok(),
+        }
+    }
+    if changed.file_name() != path.file_name() {
+        return false;
+    }
+    match (parent(changed), parent(path)) {
+        (Some(changed), Some(path)) => changed == path,
+        _ => false,
+    }
+}
+
+fn watch_dir(watcher: &mut watch::Watcher, dir: &path::Path) {
+    let dir = if dir == path::Path::new("") {
+        path::Path::new(".")
+    } else {
+        dir
+    };
+    if let Err(err) = watcher.add(dir) {
+        error!("{}: {}", dir.display(), err);
+    }
+}
+
+/// Lint everything once, then re-lint files as they change, until interrupted.
+///
+/// Reports of unchanged files are kept between cycles, so only changed files are read again,
+/// unless the lints change with `lint_path` or the accepted findings with `baseline`.
+fn run_watch<W, F>(printer: &mut printer::IoPrinter<W>,
+                   lint_path: &path::Path,
+                   baseline: Option<&path::Path>,
+                   input: &args::SearchInput,
+                   min_severity: lints::ErrorLevel,
+                   output: args::SearchOutput,
+                   stats: bool,
+                   build: F)
+                   -> Result<ActionStatus, Error>
+    where W: io::Write,
+          F: Fn(&lints::TomlLintFactory) -> Result<search::Searcher, Error>
+{
+    let mut watcher = watch::Watcher::new()?;
+    let mut searcher = build(&lints::TomlLintFactory::new_from_path(lint_path)?)?;
+    watch_dir(&mut watcher, lint_path.parent().unwrap_or(path::Path::new("")));
+    if let Some(baseline) = baseline {
+        watch_dir(&mut watcher, baseline.parent().unwrap_or(path::Path::new("")));
+    }
+
+    let template = printer.buffer();
+    // Keyed without any leading `./`, as walked paths and changed paths may differ in that
+    let mut reports: BTreeMap<path::PathBuf, FileReport> = BTreeMap::new();
+    let mut changed: Option<Vec<path::PathBuf>> = Some(Vec::new());
+    loop {
+        let started = if stats {
+            Some(time::Instant::now())
+        } else {
+            None
+        };
+        let reload = match changed {
+            Some(ref changed) => {
+                changed.iter().any(|p| {
+                    is_same_file(p, lint_path) || baseline.map_or(false, |b| is_same_file(p, b))
+                })
+            }
+            None => true,
+        };
+        if reload {
+            let rebuilt = lints::TomlLintFactory::new_from_path(lint_path)
+                .map_err(Error::from)
+                .and_then(|factory| build(&factory));
+            match rebuilt {
+                Ok(rebuilt) => {
+                    searcher = rebuilt;
+                    reports.clear();
+                }
+                Err(err) => error!("{}", err),
+            }
+        }
+        match changed {
+            Some(ref changed) => {
+                for path in changed {
+                    reports.remove(pathutil::strip_prefix("./", path).unwrap_or(path));
+                }
+            }
+            None => reports.clear(),
+        }
+
+        let mut walked = BTreeMap::new();
+        for entry in build_walker(input).build() {
+            if let Ok(ref dent) = entry {
+                match dent.file_type() {
+                    Some(ft) if ft.is_dir() => watch_dir(&mut watcher, dent.path()),
+                    Some(_) if dent.depth() == 0 => {
+                        watch_dir(&mut watcher,
+                                  dent.path().parent().unwrap_or(path::Path::new("")))
+                    }
+                    _ => {}
+                }
+            }
+            let dent = match get_or_log_dir_entry(entry) {
+                Some(dent) => dent,
+                None => continue,
+            };
+            let key = pathutil::strip_prefix("./", dent.path()).unwrap_or(dent.path()).to_path_buf();
+            let report = match reports.remove(&key) {
+                Some(report) => report,
+                None => {
+                    report_entry(&template,
+                                 search::Source::Entry(dent),
+                                 &searcher,
+                                 min_severity,
+                                 output)
+                }
+            };
+            walked.insert(key, report);
+        }
+        reports = walked;
+
+        if atty::on_stdout() {
+            printer.clear();
+        }
+        print_reports(printer,
+                      &searcher,
+                      reports.values().cloned(),
+                      output,
+                      false,
+                      started);
+        changed = watcher.wait()?;
+    }
+}
+
+fn run_lsp(lint_path: Option<path::PathBuf>) -> Result<ActionStatus, Error> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let server = lsp::Server::new(stdin.lock(), stdout.lock(), lint_path);
+    // Exiting without being shut down first is a failure, per the protocol
+    if server.run()? {
+        Ok(ActionStatus::Success)
+    } else {
+        Ok(ActionStatus::Failure)
+    }
+}
+
+/// Searcher for the lints reported at `min_reported` or above.
+fn build_searcher(factory: &lints::TomlLintFactory,
+                  min_reported: lints::ErrorLevel,
+                  report_unused_disables: bool)
+                  -> Result<search::Searcher, Error> {
+    let mut searcher = search::Searcher::new(factory.build_lints()?, min_reported)?;
+    // Unused suppression comments are reported as warnings
+    if report_unused_disables && min_reported <= lints::ErrorLevel::Warning {
+        searcher = searcher.report_unused_disables();
+    }
+    Ok(searcher)
+}
+
+fn build_walker(input: &args::SearchInput) -> ignore::WalkBuilder {
+    let mut wd = ignore::WalkBuilder::new(&input.paths[0]);
+    for path in &input.paths[1..] {
+        wd.add(path);
+    }
+    wd.follow_links(input.follow)
+        .hidden(!input.hidden)
+        .max_depth(input.maxdepth)
+        .git_global(!input.no_ignore && !input.no_ignore_vcs)
+        .git_ignore(!input.no_ignore && !input.no_ignore_vcs)
+        .git_exclude(!input.no_ignore && !input.no_ignore_vcs)
+        .ignore(!input.no_ignore)
+        .parents(!input.no_ignore_parent)
+        .threads(input.threads);
+    wd
+}
+
 fn run() -> Result<ActionStatus, Error> {
     let matches = match args::parse_args()? {
         Some(m) => m,
         None => return Ok(ActionStatus::Success),
     };
     let app = args::App::from_args(&matches)?;
-    let factory = lints::TomlLintFactory::new_from_path(&app.lint_path)?;
+    if let args::Action::Lsp = app.action {
+        return run_lsp(app.lint_path);
+    }
+    let lint_path = app.lint_path.as_ref().expect("Only the LSP server goes without lints");
+    let factory = lints::TomlLintFactory::new_from_path(lint_path)?;
 
     let stdout = std::io::stdout();
-    let mut printer =
-        printer::IoPrinter::new(stdout.lock()).use_null(app.printer.null).quiet(app.printer.quiet);
+    let mut printer = printer::IoPrinter::new(stdout.lock())
+        .use_null(app.printer.null)
+        .quiet(app.printer.quiet)
+        .format(app.printer.format)
+        .color(app.printer.color)
+        .context(app.printer.before_context, app.printer.after_context);
 
     let status: ActionStatus;
     match app.action {
-        args::Action::Search { ref input, ref min_severity, ref output } => {
-            let lints = factory.build_lints()?;
-            let mut wd = ignore::WalkBuilder::new(&input.paths[0]);
-            for path in &input.paths[1..] {
-                wd.add(path);
-            }
-            wd.follow_links(input.follow)
-                .hidden(!input.hidden)
-                .max_depth(input.maxdepth)
-                .git_global(!input.no_ignore && !input.no_ignore_vcs)
-                .git_ignore(!input.no_ignore && !input.no_ignore_vcs)
-                .git_exclude(!input.no_ignore && !input.no_ignore_vcs)
-                .ignore(!input.no_ignore)
-                .parents(!input.no_ignore_parent)
-                .threads(input.threads);
-            match *output {
-                args::SearchOutput::None => {
-                    status = run_file_one_thread(&mut printer, wd.build(), &lints)?;
+        args::Action::Search { ref input,
+                               ref min_severity,
+                               ref min_reported,
+                               ref output,
+                               stats,
+                               report_unused_disables,
+                               ref baseline,
+                               ref diff,
+                               staged,
+                               watch } => {
+            let started = if stats {
+                Some(time::Instant::now())
+            } else {
+                None
+            };
+            let build = |factory: &lints::TomlLintFactory| -> Result<search::Searcher, Error> {
+                let mut searcher = build_searcher(factory, *min_reported, report_unused_disables)?;
+                if let Some(ref baseline) = *baseline {
+                    searcher = searcher.accept_baseline(baseline::Baseline::load(baseline)?);
                 }
-                args::SearchOutput::Message => status = ActionStatus::Failure,
-                args::SearchOutput::File { matched } => status = ActionStatus::Failure,
+                Ok(searcher)
+            };
+            if watch {
+                return run_watch(&mut printer,
+                                 lint_path,
+                                 baseline.as_ref().map(|b| b.as_path()),
+                                 input,
+                                 *min_severity,
+                                 *output,
+                                 stats,
+                                 build);
+            }
+            let mut searcher = build(&factory)?;
+            if let Some(ref diff) = *diff {
+                let changes = match *diff {
+                        args::DiffSource::Git(ref rev) => changes::Changes::from_git(rev),
+                        args::DiffSource::File(ref path) => changes::Changes::from_file(path),
+                    }
+                    .map_err(errors::ArgumentError::from)?;
+                searcher = searcher.only_changes(changes);
+            }
+            let wd = build_walker(input);
+            if staged {
+                // Staged files are only linted where the walk finds them, so ignore files apply
+                let staged: BTreeMap<path::PathBuf, path::PathBuf> = git::staged_paths()
+                    .map_err(errors::ArgumentError::from)?
+                    .into_iter()
+                    .map(|path| (search::absolute(&path), path))
+                    .collect();
+                let sources = wd.build()
+                    .filter_map(get_or_log_dir_entry)
+                    .filter_map(|dent| staged.get(&search::absolute(dent.path())).cloned())
+                    .map(search::Source::Staged);
+                return run_search_one_thread(&mut printer,
+                                             sources,
+                                             &searcher,
+                                             *min_severity,
+                                             *output,
+                                             input.sort,
+                                             started);
+            }
+            if input.is_one_path() || input.threads == 1 {
+                let sources = wd.build()
+                    .filter_map(get_or_log_dir_entry)
+                    .filter(|dent| searcher.is_changed(dent.path()))
+                    .map(|dent| entry_source(dent, &input.stdin_filename));
+                status = run_search_one_thread(&mut printer,
+                                               sources,
+                                               &searcher,
+                                               *min_severity,
+                                               *output,
+                                               input.sort,
+                                               started)?;
+            } else {
+                status = run_search_parallel(&mut printer,
+                                             wd.build_parallel(),
+                                             Arc::new(searcher),
+                                             *min_severity,
+                                             *output,
+                                             input.sort,
+                                             started)?;
             }
         }
+        args::Action::WriteBaseline { ref input,
+                                      ref min_reported,
+                                      report_unused_disables,
+                                      ref path } => {
+            let searcher = build_searcher(&factory, *min_reported, report_unused_disables)?;
+            let wd = build_walker(input);
+            let sources = wd.build()
+                .filter_map(get_or_log_dir_entry)
+                .map(|dent| entry_source(dent, &input.stdin_filename));
+            status = run_write_baseline(sources, &searcher, path)?;
+        }
+        args::Action::Fix { ref input, ref min_reported, ref mode } => {
+            let lints: Vec<lints::Lint> = factory.build_lints()?
+                .into_iter()
+                .filter(|lint| lint.replace.is_some())
+                .collect();
+            let searcher = search::Searcher::new(lints, *min_reported)?;
+            let wd = build_walker(input);
+            let sources = wd.build()
+                .filter_map(get_or_log_dir_entry)
+                .map(|dent| entry_source(dent, &input.stdin_filename));
+            status = run_fix_one_thread(&mut printer, sources, &searcher, mode)?;
+        }
         args::Action::PrintTypes => {
             let types = factory.build_types()?;
             status = run_types(&mut printer, types.definitions())?
         }
+        args::Action::Lsp => unreachable!("Served above"),
     }
 
     Ok(status)
 }
 
 fn main() {
-    let drain = slog_term::streamer().compact().build().fuse();
+    let drain = slog_term::streamer().stderr().compact().build().fuse();
     let root_logger = slog::Logger::root(drain, None);
     slog_scope::set_global_logger(root_logger);
     match run() {
@@ -147,5 +796,9 @@ fn main() {
             error!("{}", e);
             std::process::exit(3)
         }
+        Err(Error::Io(ref e)) => {
+            error!("{}", e);
+            std::process::exit(4)
+        }
     }
 }
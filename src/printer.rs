@@ -1,14 +1,71 @@
+use std::cmp;
+use std::collections::BTreeMap;
 use std::io;
+use std::io::Write;
+use std::iter;
 use std::path;
+use std::time;
 
 use ignore;
+use rustc_serialize::json::{Json, ToJson};
 
+use fix;
+use lints;
 use ripgrep_stolen::pathutil;
+use search;
 
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Eq, PartialEq)]
+pub enum Format {
+    Human,
+    /// GitHub Actions workflow commands, shown as annotations
+    Github,
+    /// One JSON object per line
+    Json,
+    /// A SARIF 2.1.0 log
+    Sarif,
+    /// Checkstyle XML
+    Checkstyle,
+    /// JUnit XML, with a test suite per lint and a test case per file
+    Junit,
+    /// GitLab Code Quality (Code Climate) JSON
+    Gitlab,
+}
+
+#[derive(Clone, Default)]
+struct JunitSuite {
+    tests: usize,
+    failures: usize,
+    cases: String,
+}
+
+#[derive(Clone)]
+struct JunitCase {
+    lint: String,
+    severity: lints::ErrorLevel,
+    path: String,
+    failures: Vec<String>,
+}
+
+#[derive(Clone)]
 pub struct IoPrinter<W> {
     writer: W,
     sep: u8,
     quiet: bool,
+    format: Format,
+    color: bool,
+    /// Lines of source to show before each finding
+    before_context: usize,
+    /// Lines of source to show after each finding
+    after_context: usize,
+    /// Findings for formats that can only be written once all files are linted
+    results: Vec<Json>,
+    suites: BTreeMap<String, JunitSuite>,
+    /// Test cases of the file being printed
+    cases: Vec<JunitCase>,
+    /// Findings of the file being printed per fingerprint, to tell identical ones apart
+    occurrences: BTreeMap<u64, usize>,
 }
 
 impl<W: io::Write> IoPrinter<W> {
@@ -17,6 +74,14 @@ impl<W: io::Write> IoPrinter<W> {
             writer: writer,
             sep: b'\n',
             quiet: false,
+            format: Format::Human,
+            color: false,
+            before_context: 0,
+            after_context: 0,
+            results: Vec::new(),
+            suites: BTreeMap::new(),
+            cases: Vec::new(),
+            occurrences: BTreeMap::new(),
         }
     }
 
@@ -34,6 +99,51 @@ impl<W: io::Write> IoPrinter<W> {
         self
     }
 
+    pub fn format(mut self, format: Format) -> IoPrinter<W> {
+        self.format = format;
+        self
+    }
+
+    pub fn color(mut self, yes: bool) -> IoPrinter<W> {
+        self.color = yes;
+        self
+    }
+
+    pub fn context(mut self, before: usize, after: usize) -> IoPrinter<W> {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
+
+    /// A printer with the same settings that writes to memory.
+    pub fn buffer(&self) -> IoPrinter<Vec<u8>> {
+        IoPrinter {
+            writer: Vec::new(),
+            sep: self.sep,
+            quiet: self.quiet,
+            format: self.format,
+            color: self.color,
+            before_context: self.before_context,
+            after_context: self.after_context,
+            results: Vec::new(),
+            suites: BTreeMap::new(),
+            cases: Vec::new(),
+            occurrences: BTreeMap::new(),
+        }
+    }
+
+    /// Write out what was printed to a `buffer` printer.
+    pub fn buffered(&mut self, buffer: IoPrinter<Vec<u8>>) {
+        self.write(&buffer.writer);
+        self.results.extend(buffer.results);
+        for (lint, suite) in buffer.suites {
+            let merged = self.suites.entry(lint).or_insert_with(JunitSuite::default);
+            merged.tests += suite.tests;
+            merged.failures += suite.failures;
+            merged.cases.push_str(&suite.cases);
+        }
+    }
+
     pub fn type_def(&mut self, type_def: &ignore::types::FileTypeDef) {
         if self.quiet {
             return;
@@ -57,20 +167,676 @@ impl<W: io::Write> IoPrinter<W> {
             return;
         }
 
-        self.write(path_bytes(pathutil::strip_prefix("./", path).unwrap_or(path)));
+        self.write_path(path);
         self.write_sep();
     }
 
+    pub fn count(&mut self, path: &path::Path, count: usize) {
+        if self.quiet {
+            return;
+        }
+
+        self.write_path(path);
+        self.write_count(count);
+    }
+
+    pub fn lint_count(&mut self, lint: &lints::Lint, count: usize) {
+        if self.quiet {
+            return;
+        }
+
+        self.write(lint.name.as_bytes());
+        self.write_count(count);
+    }
+
+    /// Start of the whole-run document, printed before any file.
+    pub fn begin(&mut self) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            Format::Human | Format::Github | Format::Json | Format::Sarif | Format::Junit |
+            Format::Gitlab => {}
+            Format::Checkstyle => {
+                self.write(b"<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+                self.write(b"<checkstyle version=\"4.3\">\n");
+            }
+        }
+    }
+
+    /// Start of the violations for a file checked against `lints`.
+    pub fn begin_file(&mut self, path: &path::Path, lints: &[&lints::Lint]) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            Format::Human | Format::Github | Format::Json | Format::Sarif => {}
+            Format::Gitlab => self.occurrences.clear(),
+            Format::Checkstyle => {
+                let name = xml_escape(&display_path(path).to_string_lossy());
+                self.write(format!("<file name=\"{}\">\n", name).as_bytes());
+            }
+            Format::Junit => {
+                let path = display_path(path).to_string_lossy().into_owned();
+                self.cases = lints.iter()
+                    .map(|lint| {
+                        JunitCase {
+                            lint: lint.name.clone(),
+                            severity: lint.severity,
+                            path: path.clone(),
+                            failures: Vec::new(),
+                        }
+                    })
+                    .collect();
+            }
+        }
+    }
+
+    /// End of the violations for a linted file.
+    pub fn end_file(&mut self) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            Format::Human | Format::Github | Format::Json | Format::Sarif | Format::Gitlab => {}
+            Format::Checkstyle => self.write(b"</file>\n"),
+            Format::Junit => {
+                for case in self.cases.drain(..) {
+                    let suite = self.suites.entry(case.lint.clone()).or_insert_with(JunitSuite::default);
+                    suite.tests += 1;
+                    let name = xml_escape(&case.path);
+                    let classname = xml_escape(&case.lint);
+                    if case.failures.is_empty() {
+                        suite.cases.push_str(&format!("    <testcase name=\"{}\" classname=\"{}\"/>\n",
+                                                      name,
+                                                      classname));
+                    } else {
+                        suite.failures += 1;
+                        suite.cases.push_str(&format!("    <testcase name=\"{}\" classname=\"{}\">\n",
+                                                      name,
+                                                      classname));
+                        suite.cases.push_str(&format!("      <failure message=\"{} violation(s)\" \
+                                                       type=\"{}\">{}</failure>\n",
+                                                      case.failures.len(),
+                                                      case.severity.as_str(),
+                                                      xml_escape(&case.failures.join("\n"))));
+                        suite.cases.push_str("    </testcase>\n");
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn violation(&mut self,
+                     path: &path::Path,
+                     content: &[u8],
+                     violation: &search::Violation) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            Format::Human => self.human_violation(path, content, violation),
+            Format::Github => self.github_violation(path, content, violation),
+            Format::Json => self.json_violation(path, content, violation),
+            Format::Sarif => {
+                let result = sarif_result(path, content, violation);
+                self.results.push(result);
+            }
+            Format::Gitlab => {
+                let fingerprint = gitlab_fingerprint(path, content, violation);
+                let occurrence = {
+                    let count = self.occurrences.entry(fingerprint).or_insert(0);
+                    *count += 1;
+                    *count
+                };
+                let fingerprint = if occurrence == 1 {
+                    format!("{:016x}", fingerprint)
+                } else {
+                    format!("{:016x}-{}", fingerprint, occurrence)
+                };
+                self.results.push(gitlab_issue(path, violation, fingerprint));
+            }
+            Format::Junit => {
+                let failure = format!("{}:{}: {}",
+                                      violation.line,
+                                      violation.column,
+                                      String::from_utf8_lossy(&violation.lint.message));
+                if let Some(case) = self.cases
+                    .iter_mut()
+                    .find(|case| case.lint == violation.lint.name) {
+                    case.failures.push(failure);
+                }
+            }
+            Format::Checkstyle => {
+                let error = format!("<error line=\"{}\" column=\"{}\" severity=\"{}\" \
+                                     message=\"{}\" source=\"{}\"/>\n",
+                                    violation.line,
+                                    violation.column,
+                                    violation.lint.severity.as_str(),
+                                    xml_escape(&String::from_utf8_lossy(&violation.lint.message)),
+                                    xml_escape(&violation.lint.name));
+                self.write(error.as_bytes());
+            }
+        }
+    }
+
+    /// Totals and any whole-run document, printed once all files have been linted with
+    /// `searcher`.
+    ///
+    /// With `elapsed`, from `--stats`, the JSON summary includes the statistics.
+    pub fn finish(&mut self,
+                  searcher: &search::Searcher,
+                  summary: &search::Summary,
+                  elapsed: Option<time::Duration>) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            Format::Human | Format::Github => {}
+            Format::Sarif => {
+                let results = self.results.drain(..).collect();
+                // Every lint, so rule indexes don't depend on the report level
+                self.write_json(sarif_log(&searcher.rules(), results));
+            }
+            Format::Gitlab => {
+                let issues = Json::Array(self.results.drain(..).collect());
+                self.write_json(issues);
+            }
+            Format::Checkstyle => self.write(b"</checkstyle>\n"),
+            Format::Junit => {
+                let tests: usize = self.suites.values().map(|s| s.tests).sum();
+                let failures: usize = self.suites.values().map(|s| s.failures).sum();
+                let mut document = String::new();
+                document.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+                document.push_str(&format!("<testsuites name=\"relint\" tests=\"{}\" \
+                                            failures=\"{}\">\n",
+                                           tests,
+                                           failures));
+                for lint in searcher.reported() {
+                    let suite = self.suites.remove(&lint.name).unwrap_or_else(JunitSuite::default);
+                    document.push_str(&format!("  <testsuite name=\"{}\" tests=\"{}\" \
+                                                failures=\"{}\">\n",
+                                               xml_escape(&lint.name),
+                                               suite.tests,
+                                               suite.failures));
+                    document.push_str(&suite.cases);
+                    document.push_str("  </testsuite>\n");
+                }
+                document.push_str("</testsuites>\n");
+                self.write(document.as_bytes());
+            }
+            Format::Json => {
+                let mut record = BTreeMap::new();
+                record.insert("type".to_string(), "summary".to_json());
+                record.insert("files".to_string(), summary.files.to_json());
+                record.insert("findings".to_string(), summary.findings().to_json());
+                record.insert("errors".to_string(), summary.errors.to_json());
+                record.insert("warnings".to_string(), summary.warnings.to_json());
+                record.insert("infos".to_string(), summary.infos.to_json());
+                if let Some(elapsed) = elapsed {
+                    let counts: BTreeMap<String, Json> = searcher.reported()
+                        .iter()
+                        .map(|lint| {
+                            let count = summary.lints.get(&lint.name).cloned().unwrap_or(0);
+                            (lint.name.clone(), count.to_json())
+                        })
+                        .collect();
+                    record.insert("walked".to_string(), summary.walked.to_json());
+                    record.insert("skipped".to_string(), summary.skipped.to_json());
+                    record.insert("bytes".to_string(), summary.bytes.to_json());
+                    record.insert("elapsed".to_string(), seconds(elapsed).to_json());
+                    record.insert("lints".to_string(), Json::Object(counts));
+                }
+                self.write_json(Json::Object(record));
+            }
+        }
+    }
+
+    /// Statistics about the run, printed last.
+    ///
+    /// JSON output gets them in the summary record instead, see `finish`.
+    pub fn stats(&mut self,
+                 lints: &[&lints::Lint],
+                 summary: &search::Summary,
+                 elapsed: time::Duration) {
+        if self.quiet {
+            return;
+        }
+
+        match self.format {
+            Format::Human | Format::Github => {
+                let table = stats_table(lints, summary, elapsed);
+                self.write(table.as_bytes());
+            }
+            Format::Json => {}
+            Format::Sarif | Format::Checkstyle | Format::Junit | Format::Gitlab => {
+                // Keep the document on stdout valid.
+                let table = stats_table(lints, summary, elapsed);
+                let _ = io::stderr().write_all(table.as_bytes());
+            }
+        }
+    }
+
+    /// A compiler-style diagnostic, with the offending source line and the match underlined.
+    fn human_violation(&mut self,
+                       path: &path::Path,
+                       content: &[u8],
+                       violation: &search::Violation) {
+        let style = match violation.lint.severity {
+            lints::ErrorLevel::Error => RED,
+            lints::ErrorLevel::Warning => YELLOW,
+            lints::ErrorLevel::Info => CYAN,
+        };
+        let header = format!("{}[{}]", violation.lint.severity.as_str(), violation.lint.name);
+        self.paint(style, header.as_bytes());
+        let mut message = b": ".to_vec();
+        message.extend_from_slice(&violation.lint.message);
+        self.paint(BOLD, &message);
+        self.write(b"\n");
+
+        let line_start = violation.start - (violation.column - 1);
+        let line_end = line_end_at(content, line_start);
+        let mut before = Vec::new();
+        let mut start = line_start;
+        while before.len() < self.before_context && 0 < start {
+            let previous = line_start_at(content, start - 1);
+            before.push(&content[previous..start]);
+            start = previous;
+        }
+        before.reverse();
+        let mut after = Vec::new();
+        let mut end = line_end;
+        while after.len() < self.after_context && end < content.len() {
+            let next = line_end_at(content, end);
+            after.push(&content[end..next]);
+            end = next;
+        }
+
+        let width = (violation.line + after.len()).to_string().len();
+        let gutter: String = iter::repeat(' ').take(width).collect();
+        self.paint(BLUE, format!("{}--> ", gutter).as_bytes());
+        self.write_path(path);
+        self.write(format!(":{}:{}\n", violation.line, violation.column).as_bytes());
+        self.paint(BLUE, format!("{} |", gutter).as_bytes());
+        self.write(b"\n");
+        let first = violation.line - before.len();
+        for (i, line) in before.iter().enumerate() {
+            self.source_line(first + i, width, line);
+        }
+        self.source_line(violation.line, width, &content[line_start..line_end]);
+
+        let matched_end = cmp::min(violation.end, line_end);
+        let prefix = String::from_utf8_lossy(&content[line_start..violation.start]);
+        let matched = String::from_utf8_lossy(strip_newline(&content[violation.start..matched_end]));
+        let padding: String = prefix.chars().map(|c| if c == '\t' { '\t' } else { ' ' }).collect();
+        let carets: String = iter::repeat('^')
+            .take(cmp::max(matched.chars().count(), 1))
+            .collect();
+        self.paint(BLUE, format!("{} | ", gutter).as_bytes());
+        self.write(padding.as_bytes());
+        self.paint(style, carets.as_bytes());
+        self.write(b"\n");
+
+        for (i, line) in after.iter().enumerate() {
+            self.source_line(violation.line + 1 + i, width, line);
+        }
+        self.write(b"\n");
+    }
+
+    fn source_line(&mut self, number: usize, width: usize, line: &[u8]) {
+        self.paint(BLUE, format!("{:>width$} | ", number, width = width).as_bytes());
+        self.write(strip_newline(line));
+        self.write(b"\n");
+    }
+
+    /// Write `text` in `style`, if colour is enabled.
+    fn paint(&mut self, style: &str, text: &[u8]) {
+        if self.color {
+            self.write(style.as_bytes());
+            self.write(text);
+            self.write(RESET.as_bytes());
+        } else {
+            self.write(text);
+        }
+    }
+
+    /// An annotation, with columns counted in characters.
+    fn github_violation(&mut self,
+                        path: &path::Path,
+                        content: &[u8],
+                        violation: &search::Violation) {
+        let (column, end_column) = char_columns(content, violation);
+        let command = match violation.lint.severity {
+            lints::ErrorLevel::Error => "error",
+            lints::ErrorLevel::Warning => "warning",
+            lints::ErrorLevel::Info => "notice",
+        };
+        let annotation = format!("::{} file={},line={},col={},endColumn={},title={}::{}\n",
+                                 command,
+                                 github_escape_property(&display_path(path).to_string_lossy()),
+                                 violation.line,
+                                 column,
+                                 end_column,
+                                 github_escape_property(&violation.lint.name),
+                                 github_escape(&String::from_utf8_lossy(&violation.lint.message)));
+        self.write(annotation.as_bytes());
+    }
+
+    fn json_violation(&mut self,
+                      path: &path::Path,
+                      content: &[u8],
+                      violation: &search::Violation) {
+        let matched = &content[violation.start..violation.end];
+        let mut record = BTreeMap::new();
+        record.insert("type".to_string(), "finding".to_json());
+        record.insert("path".to_string(),
+                      display_path(path).to_string_lossy().to_json());
+        record.insert("line".to_string(), violation.line.to_json());
+        record.insert("column_start".to_string(), violation.column.to_json());
+        record.insert("column_end".to_string(),
+                      (violation.column + matched.len()).to_json());
+        record.insert("byte_start".to_string(), violation.start.to_json());
+        record.insert("byte_end".to_string(), violation.end.to_json());
+        record.insert("lint".to_string(), violation.lint.name.to_json());
+        record.insert("severity".to_string(),
+                      violation.lint.severity.as_str().to_json());
+        record.insert("message".to_string(),
+                      String::from_utf8_lossy(&violation.lint.message).to_json());
+        record.insert("matched".to_string(), String::from_utf8_lossy(matched).to_json());
+        record.insert("replacement".to_string(),
+                      violation.replacement
+                          .as_ref()
+                          .map(|r| String::from_utf8_lossy(r).into_owned())
+                          .to_json());
+        self.write_json(Json::Object(record));
+    }
+
+    fn write_json(&mut self, record: Json) {
+        self.write(record.to_string().as_bytes());
+        self.write(b"\n");
+    }
+
+    /// Unified diff, in a form `patch -p1` accepts.
+    pub fn diff(&mut self, path: &path::Path, hunks: &[fix::Hunk]) {
+        if self.quiet || hunks.is_empty() {
+            return;
+        }
+
+        self.write(b"--- a/");
+        self.write_path(path);
+        self.write(b"\n+++ b/");
+        self.write_path(path);
+        self.write(b"\n");
+        for hunk in hunks {
+            let header = format!("@@ -{},{} +{},{} @@\n",
+                                 hunk.old_start,
+                                 hunk.old_count,
+                                 hunk.new_start,
+                                 hunk.new_count);
+            self.write(header.as_bytes());
+            for line in &hunk.lines {
+                let (prefix, content) = match *line {
+                    fix::DiffLine::Context(ref c) => (b" ", c),
+                    fix::DiffLine::Removed(ref c) => (b"-", c),
+                    fix::DiffLine::Added(ref c) => (b"+", c),
+                };
+                self.write(prefix);
+                self.write(content);
+                if !content.ends_with(b"\n") {
+                    self.write(b"\n\\ No newline at end of file\n");
+                }
+            }
+        }
+    }
+
+    /// Clear the terminal, so a new report replaces the last one.
+    pub fn clear(&mut self) {
+        self.write(CLEAR.as_bytes());
+        let _ = self.writer.flush();
+    }
+
+    pub fn prompt(&mut self, question: &str) {
+        self.write(question.as_bytes());
+        let _ = self.writer.flush();
+    }
+
+    fn write_path(&mut self, path: &path::Path) {
+        self.write(path_bytes(display_path(path)));
+    }
+
     fn write(&mut self, buf: &[u8]) {
         let _ = self.writer.write_all(buf);
     }
 
+    /// With `--null`, the NUL takes the place of the `:` so names can contain anything.
+    fn write_count(&mut self, count: usize) {
+        if self.sep == b'\0' {
+            self.write(b"\0");
+        } else {
+            self.write(b":");
+        }
+        self.write(format!("{}\n", count).as_bytes());
+    }
+
     fn write_sep(&mut self) {
         let sep = self.sep;
         self.write(&[sep]);
     }
 }
 
+static CLEAR: &'static str = "\x1b[2J\x1b[H";
+static RESET: &'static str = "\x1b[0m";
+static BOLD: &'static str = "\x1b[1m";
+static RED: &'static str = "\x1b[1;31m";
+static YELLOW: &'static str = "\x1b[1;33m";
+static BLUE: &'static str = "\x1b[1;34m";
+static CYAN: &'static str = "\x1b[1;36m";
+
+/// Offset of the start of the line containing `offset`.
+fn line_start_at(content: &[u8], offset: usize) -> usize {
+    content[..offset].iter().rposition(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0)
+}
+
+/// Offset just past the end of the line starting at `start`, including its terminator.
+fn line_end_at(content: &[u8], start: usize) -> usize {
+    content[start..].iter().position(|b| *b == b'\n').map(|i| start + i + 1).unwrap_or(content.len())
+}
+
+fn strip_newline(line: &[u8]) -> &[u8] {
+    let line = if line.ends_with(b"\n") { &line[..line.len() - 1] } else { line };
+    if line.ends_with(b"\r") { &line[..line.len() - 1] } else { line }
+}
+
+fn seconds(duration: time::Duration) -> f64 {
+    duration.as_secs() as f64 + duration.subsec_nanos() as f64 / 1_000_000_000.0
+}
+
+fn stats_table(lints: &[&lints::Lint], summary: &search::Summary, elapsed: time::Duration) -> String {
+    let mut table = String::new();
+    table.push_str(&format!("files walked:  {}\n", summary.walked));
+    table.push_str(&format!("files checked: {}\n", summary.files));
+    table.push_str(&format!("files skipped: {}\n", summary.skipped));
+    table.push_str(&format!("bytes scanned: {}\n", summary.bytes));
+    table.push_str(&format!("elapsed:       {:.3}s\n", seconds(elapsed)));
+
+    let width = lints.iter().map(|lint| lint.name.len()).fold("severity".len(), cmp::max);
+    table.push_str(&format!("\n{:<width$}  findings\n", "lint", width = width));
+    for lint in lints {
+        let count = summary.lints.get(&lint.name).cloned().unwrap_or(0);
+        table.push_str(&format!("{:<width$}  {}\n", lint.name, count, width = width));
+    }
+    table.push_str(&format!("\n{:<width$}  findings\n", "severity", width = width));
+    for &(severity, count) in &[(lints::ErrorLevel::Error, summary.errors),
+                                (lints::ErrorLevel::Warning, summary.warnings),
+                                (lints::ErrorLevel::Info, summary.infos)] {
+        table.push_str(&format!("{:<width$}  {}\n", severity.as_str(), count, width = width));
+    }
+    table
+}
+
+fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            '\t' | '\n' | '\r' => escaped.push_str(&format!("&#{};", c as u32)),
+            c if c < ' ' => escaped.push('\u{FFFD}'),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escape workflow command data.
+fn github_escape(s: &str) -> String {
+    s.replace('%', "%25").replace('\r', "%0D").replace('\n', "%0A")
+}
+
+/// Escape a workflow command property value, which also can't contain its delimiters.
+fn github_escape_property(s: &str) -> String {
+    github_escape(s).replace(':', "%3A").replace(',', "%2C")
+}
+
+pub fn json_object(fields: Vec<(&str, Json)>) -> Json {
+    Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+}
+
+fn sarif_level(severity: lints::ErrorLevel) -> &'static str {
+    match severity {
+        lints::ErrorLevel::Error => "error",
+        lints::ErrorLevel::Warning => "warning",
+        lints::ErrorLevel::Info => "note",
+    }
+}
+
+fn sarif_log(lints: &[&lints::Lint], results: Vec<Json>) -> Json {
+    let rules = lints.iter()
+        .map(|lint| {
+            let message = String::from_utf8_lossy(&lint.message).into_owned();
+            json_object(vec![("id", lint.name.to_json()),
+                             ("shortDescription", json_object(vec![("text", message.to_json())])),
+                             ("defaultConfiguration",
+                              json_object(vec![("level", sarif_level(lint.severity).to_json())]))])
+        })
+        .collect();
+    let driver = json_object(vec![("name", "relint".to_json()),
+                                  ("version", crate_version!().to_json()),
+                                  ("informationUri", "https://github.com/epage/relint".to_json()),
+                                  ("rules", Json::Array(rules))]);
+    let run = json_object(vec![("tool", json_object(vec![("driver", driver)])),
+                               ("columnKind", "unicodeCodePoints".to_json()),
+                               ("results", Json::Array(results))]);
+    json_object(vec![("$schema",
+                      "https://json.schemastore.org/sarif-2.1.0.json".to_json()),
+                     ("version", "2.1.0".to_json()),
+                     ("runs", Json::Array(vec![run]))])
+}
+
+/// 1-based start and end column of a violation, counted in code points.
+fn char_columns(content: &[u8], violation: &search::Violation) -> (usize, usize) {
+    let line_start = violation.start + 1 - violation.column;
+    let start_column = String::from_utf8_lossy(&content[line_start..violation.start])
+        .chars()
+        .count() + 1;
+    let end_column = start_column +
+                     String::from_utf8_lossy(&content[violation.start..violation.end])
+        .chars()
+        .count();
+    (start_column, end_column)
+}
+
+/// Region of a violation, with columns counted in code points.
+fn sarif_region(content: &[u8], violation: &search::Violation) -> Json {
+    let (start_column, end_column) = char_columns(content, violation);
+    json_object(vec![("startLine", violation.line.to_json()),
+                     ("startColumn", start_column.to_json()),
+                     ("endLine", violation.line.to_json()),
+                     ("endColumn", end_column.to_json()),
+                     ("byteOffset", violation.start.to_json()),
+                     ("byteLength", (violation.end - violation.start).to_json())])
+}
+
+fn sarif_result(path: &path::Path, content: &[u8], violation: &search::Violation) -> Json {
+    let uri = display_path(path).to_string_lossy().replace('\\', "/");
+    let artifact = json_object(vec![("uri", uri.to_json())]);
+    let region = sarif_region(content, violation);
+    let message = String::from_utf8_lossy(&violation.lint.message).into_owned();
+    let location =
+        json_object(vec![("physicalLocation",
+                          json_object(vec![("artifactLocation", artifact.clone()),
+                                           ("region", region.clone())]))]);
+    let mut fields = vec![("ruleId", violation.lint.name.to_json()),
+                          ("level", sarif_level(violation.lint.severity).to_json()),
+                          ("message", json_object(vec![("text", message.to_json())])),
+                          ("locations", Json::Array(vec![location]))];
+    if let Some(ref replacement) = violation.replacement {
+        let inserted = String::from_utf8_lossy(replacement).into_owned();
+        let replacement = json_object(vec![("deletedRegion", region),
+                                           ("insertedContent",
+                                            json_object(vec![("text", inserted.to_json())]))]);
+        let change = json_object(vec![("artifactLocation", artifact),
+                                      ("replacements", Json::Array(vec![replacement]))]);
+        let fix = json_object(vec![("description",
+                                    json_object(vec![("text", message.to_json())])),
+                                   ("artifactChanges", Json::Array(vec![change]))]);
+        fields.push(("fixes", Json::Array(vec![fix])));
+    }
+    json_object(fields)
+}
+
+fn gitlab_severity(severity: lints::ErrorLevel) -> &'static str {
+    match severity {
+        lints::ErrorLevel::Error => "major",
+        lints::ErrorLevel::Warning => "minor",
+        lints::ErrorLevel::Info => "info",
+    }
+}
+
+/// Identify a finding by the line it is on and what matched rather than where, so it survives
+/// lines being added or removed around it.
+fn gitlab_fingerprint(path: &path::Path, content: &[u8], violation: &search::Violation) -> u64 {
+    let line_start = violation.start + 1 - violation.column;
+    let line_end = content[line_start..]
+        .iter()
+        .position(|b| *b == b'\n')
+        .map(|i| line_start + i)
+        .unwrap_or(content.len());
+    let mut hash = search::FNV_OFFSET;
+    hash = search::fnv1a(hash, violation.lint.name.as_bytes());
+    hash = search::fnv1a(hash, b"\0");
+    hash = search::fnv1a(hash, path_bytes(display_path(path)));
+    hash = search::fnv1a(hash, b"\0");
+    hash = search::fnv1a_normalized(hash, &content[line_start..line_end]);
+    hash = search::fnv1a(hash, b"\0");
+    search::fnv1a_normalized(hash, &content[violation.start..violation.end])
+}
+
+fn gitlab_issue(path: &path::Path, violation: &search::Violation, fingerprint: String) -> Json {
+    let lines = json_object(vec![("begin", violation.line.to_json())]);
+    let location = json_object(vec![("path", display_path(path).to_string_lossy().to_json()),
+                                    ("lines", lines)]);
+    json_object(vec![("type", "issue".to_json()),
+                     ("check_name", violation.lint.name.to_json()),
+                     ("description",
+                      String::from_utf8_lossy(&violation.lint.message).to_json()),
+                     ("severity", gitlab_severity(violation.lint.severity).to_json()),
+                     ("fingerprint", fingerprint.to_json()),
+                     ("location", location)])
+}
+
+fn display_path(path: &path::Path) -> &path::Path {
+    pathutil::strip_prefix("./", path).unwrap_or(path)
+}
+
 #[cfg(unix)]
 fn path_bytes<'a>(path: &'a path::Path) -> &'a [u8] {
     use std::os::unix::ffi::OsStrExt;
@@ -0,0 +1,400 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::path;
+
+use ignore;
+use regex::bytes;
+
+use baseline;
+use changes;
+use errors;
+use git;
+use lints;
+use suppress;
+
+/// A single place where a lint's pattern matched.
+pub struct Violation<'l> {
+    pub lint: &'l lints::Lint,
+    /// 1-based line number
+    pub line: usize,
+    /// 1-based byte offset within the line
+    pub column: usize,
+    /// Byte offset of the match within the file
+    pub start: usize,
+    /// Byte offset of the end of the match within the file
+    pub end: usize,
+    /// The lint's `replace` template, expanded for this match
+    pub replacement: Option<Vec<u8>>,
+}
+
+impl<'l> Violation<'l> {
+    fn new(lint: &'l lints::Lint,
+           index: &LineIndex,
+           start: usize,
+           end: usize,
+           replacement: Option<Vec<u8>>)
+           -> Violation<'l> {
+        let (line, column) = index.position(start);
+        Violation {
+            lint: lint,
+            line: line,
+            column: column,
+            start: start,
+            end: end,
+            replacement: replacement,
+        }
+    }
+}
+
+/// Totals across linted files.
+#[derive(Clone, Debug, Default)]
+pub struct Summary {
+    /// Files found by the walk
+    pub walked: usize,
+    /// Files linted
+    pub files: usize,
+    /// Files no lint applies to
+    pub skipped: usize,
+    pub bytes: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub infos: usize,
+    /// Findings per lint name
+    pub lints: BTreeMap<String, usize>,
+}
+
+impl Summary {
+    pub fn add_violation(&mut self, violation: &Violation) {
+        match violation.lint.severity {
+            lints::ErrorLevel::Error => self.errors += 1,
+            lints::ErrorLevel::Warning => self.warnings += 1,
+            lints::ErrorLevel::Info => self.infos += 1,
+        }
+        *self.lints.entry(violation.lint.name.clone()).or_insert(0) += 1;
+    }
+
+    pub fn merge(&mut self, other: &Summary) {
+        self.walked += other.walked;
+        self.files += other.files;
+        self.skipped += other.skipped;
+        self.bytes += other.bytes;
+        self.errors += other.errors;
+        self.warnings += other.warnings;
+        self.infos += other.infos;
+        for (lint, count) in &other.lints {
+            *self.lints.entry(lint.clone()).or_insert(0) += *count;
+        }
+    }
+
+    pub fn findings(&self) -> usize {
+        self.errors + self.warnings + self.infos
+    }
+}
+
+/// Maps byte offsets to line / column positions.
+pub struct LineIndex {
+    starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(content: &[u8]) -> LineIndex {
+        let mut starts = vec![0];
+        starts.extend(content.iter()
+            .enumerate()
+            .filter(|&(_, b)| *b == b'\n')
+            .map(|(i, _)| i + 1));
+        LineIndex { starts: starts }
+    }
+
+    /// 1-based line and column for `offset`.
+    pub fn position(&self, offset: usize) -> (usize, usize) {
+        let line = match self.starts.binary_search(&offset) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        (line + 1, offset - self.starts[line] + 1)
+    }
+}
+
+pub static FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+/// FNV-1a, so fingerprints stay the same across builds and platforms.
+pub fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, b| (hash ^ *b as u64).wrapping_mul(0x100000001b3))
+}
+
+/// `fnv1a` of `text` with whitespace collapsed, so re-indenting doesn't change the hash.
+pub fn fnv1a_normalized(mut hash: u64, text: &[u8]) -> u64 {
+    let text = String::from_utf8_lossy(text);
+    for (i, word) in text.split_whitespace().enumerate() {
+        if 0 < i {
+            hash = fnv1a(hash, b" ");
+        }
+        hash = fnv1a(hash, word.as_bytes());
+    }
+    hash
+}
+
+pub fn read_entry(dent: &ignore::DirEntry) -> io::Result<Vec<u8>> {
+    if dent.is_stdin() {
+        return read_stdin();
+    }
+    let mut content = Vec::new();
+    let mut f = fs::File::open(dent.path())?;
+    f.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+fn read_stdin() -> io::Result<Vec<u8>> {
+    let mut content = Vec::new();
+    let stdin = io::stdin();
+    let mut stdin = stdin.lock();
+    stdin.read_to_end(&mut content)?;
+    Ok(content)
+}
+
+/// `path` made absolute, resolving symbolic links in its directory but not in its name.
+pub fn absolute(path: &path::Path) -> path::PathBuf {
+    let resolved = match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) if dir != path::Path::new("") => {
+            dir.canonicalize().map(|dir| dir.join(name))
+        }
+        _ => path::Path::new(".").canonicalize().map(|dir| dir.join(path)),
+    };
+    resolved.unwrap_or_else(|_| {
+        env::current_dir().map(|cwd| cwd.join(path)).unwrap_or_else(|_| path.to_path_buf())
+    })
+}
+
+/// A file to lint.
+pub enum Source {
+    /// Found by the walk, or stdin
+    Entry(ignore::DirEntry),
+    /// Stdin, standing in for the given path
+    Stdin(path::PathBuf),
+    /// Content staged in the git index
+    Staged(path::PathBuf),
+}
+
+impl Source {
+    /// Path to match lint types against and report.
+    pub fn path(&self) -> &path::Path {
+        match *self {
+            Source::Entry(ref dent) => dent.path(),
+            Source::Stdin(ref path) |
+            Source::Staged(ref path) => path,
+        }
+    }
+
+    pub fn is_stdin(&self) -> bool {
+        match *self {
+            Source::Entry(ref dent) => dent.is_stdin(),
+            Source::Stdin(_) => true,
+            Source::Staged(_) => false,
+        }
+    }
+
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        match *self {
+            Source::Entry(ref dent) => read_entry(dent),
+            Source::Stdin(_) => read_stdin(),
+            Source::Staged(ref path) => git::show_staged(path),
+        }
+    }
+}
+
+/// Lints to search for, with a combined prefilter so each line is scanned once for all of them.
+pub struct Searcher {
+    /// Lints reported, which are the ones searched for
+    pub lints: Vec<lints::Lint>,
+    /// Every configured lint, including those below the report level
+    pub configured: Vec<lints::Lint>,
+    prefilter: bytes::RegexSet,
+    directive: bytes::Regex,
+    /// The `UNUSED_DISABLE` built-in lint, when reporting unused suppression comments
+    unused_disable: Option<lints::Lint>,
+    baseline: Option<baseline::Baseline>,
+    changes: Option<changes::Changes>,
+}
+
+impl Searcher {
+    /// Searcher for the `configured` lints reported at `min_reported` or above.
+    pub fn new(configured: Vec<lints::Lint>,
+               min_reported: lints::ErrorLevel)
+               -> Result<Searcher, errors::ConfigError> {
+        let lints: Vec<lints::Lint> = configured.iter()
+            .filter(|lint| min_reported <= lint.severity)
+            .cloned()
+            .collect();
+        let patterns: Vec<String> = lints.iter()
+            .map(|lint| format!("(?m){}", lint.pattern.regex().as_str()))
+            .collect();
+        let prefilter = bytes::RegexSet::new(&patterns)?;
+        Ok(Searcher {
+            lints: lints,
+            configured: configured,
+            prefilter: prefilter,
+            directive: suppress::directive(),
+            unused_disable: None,
+            baseline: None,
+            changes: None,
+        })
+    }
+
+    /// Only report findings on lines in `changes`.
+    pub fn only_changes(mut self, changes: changes::Changes) -> Searcher {
+        self.changes = Some(changes);
+        self
+    }
+
+    /// Whether `path` has lines to report findings on, which with `only_changes` means it
+    /// changed.
+    pub fn is_changed(&self, path: &path::Path) -> bool {
+        self.changes.as_ref().map(|changes| changes.touches(path)).unwrap_or(true)
+    }
+
+    /// Leave out findings recorded in `baseline`.
+    pub fn accept_baseline(mut self, baseline: baseline::Baseline) -> Searcher {
+        self.baseline = Some(baseline);
+        self
+    }
+
+    /// Also report suppression comments that suppress nothing or name a lint that isn't
+    /// configured, as findings of the `UNUSED_DISABLE` built-in lint.
+    pub fn report_unused_disables(mut self) -> Searcher {
+        self.unused_disable = Some(lints::Lint::builtin(lints::UNUSED_DISABLE,
+                                                        suppress::DIRECTIVE,
+                                                        lints::ErrorLevel::Warning,
+                                                        "Suppression comment is unused or names \
+                                                         an unknown lint"));
+        self
+    }
+
+    /// Lints findings are reported for, including any built-in one.
+    pub fn reported(&self) -> Vec<&lints::Lint> {
+        self.lints.iter().chain(self.unused_disable.iter()).collect()
+    }
+
+    /// Every configured lint, including those below the report level, and any built-in one.
+    pub fn rules(&self) -> Vec<&lints::Lint> {
+        self.configured.iter().chain(self.unused_disable.iter()).collect()
+    }
+
+    /// Lints that apply to `path`.
+    ///
+    /// Suppression comments are only checked in files a configured lint applies to.
+    pub fn applicable(&self, path: &path::Path) -> Vec<&lints::Lint> {
+        let mut applicable: Vec<&lints::Lint> =
+            self.lints.iter().filter(|lint| lint.applies_to(path)).collect();
+        if !applicable.is_empty() {
+            applicable.extend(self.unused_disable.iter());
+        }
+        applicable
+    }
+
+    /// Whether any configured lint applies to `path`.
+    pub fn applies_to(&self, path: &path::Path) -> bool {
+        self.lints.iter().any(|lint| lint.applies_to(path))
+    }
+
+    /// Find all violations of the lints that apply to `path` within `content`, ordered by
+    /// position.
+    ///
+    /// Violations covered by a suppression comment, in the baseline or outside of the changed
+    /// lines are left out.
+    pub fn search(&self, path: &path::Path, content: &[u8]) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        let check_disables = self.unused_disable.is_some() && self.applies_to(path) &&
+                             self.directive.is_match(content);
+        if !self.prefilter.is_match(content) && !check_disables {
+            return violations;
+        }
+
+        let applicable: Vec<bool> = self.lints.iter().map(|lint| lint.applies_to(path)).collect();
+        let index = LineIndex::new(content);
+        for (i, &line_start) in index.starts.iter().enumerate() {
+            if content.len() <= line_start {
+                break;
+            }
+            let line_end = index.starts.get(i + 1).cloned().unwrap_or(content.len());
+            let text = &content[line_start..line_end];
+            let matched = self.prefilter.matches(text);
+            if !matched.matched_any() {
+                continue;
+            }
+            for l in matched.into_iter().filter(|l| applicable[*l]) {
+                let lint = &self.lints[l];
+                let re = lint.pattern.regex();
+                match lint.replace {
+                    Some(ref replace) => {
+                        for caps in re.captures_iter(text) {
+                            let (start, end) = caps.pos(0).expect("Group 0 is always present");
+                            let mut replacement = Vec::new();
+                            caps.expand(replace, &mut replacement);
+                            violations.push(Violation::new(lint,
+                                                           &index,
+                                                           line_start + start,
+                                                           line_start + end,
+                                                           Some(replacement)));
+                        }
+                    }
+                    None => {
+                        for (start, end) in re.find_iter(text) {
+                            violations.push(Violation::new(lint,
+                                                           &index,
+                                                           line_start + start,
+                                                           line_start + end,
+                                                           None));
+                        }
+                    }
+                }
+            }
+        }
+        violations.sort_by_key(|v| (v.start, v.end));
+        if violations.is_empty() && !check_disables {
+            return violations;
+        }
+
+        let suppressions = suppress::parse(&self.directive, content, &index);
+        let mut used = vec![false; suppressions.len()];
+        violations.retain(|v| {
+            let mut covered = false;
+            for (i, suppression) in suppressions.iter().enumerate() {
+                if suppression.covers(v) {
+                    used[i] = true;
+                    covered = true;
+                }
+            }
+            !covered
+        });
+        if let Some(ref unused_disable) = self.unused_disable {
+            for (suppression, used) in suppressions.iter().zip(used) {
+                let unknown = suppression.lints
+                    .iter()
+                    .any(|l| !self.configured.iter().any(|lint| lint.name == *l));
+                // Lints left out by `--report-level` might have used it.
+                let searched = suppression.lints
+                    .iter()
+                    .all(|l| self.lints.iter().any(|lint| lint.name == *l));
+                if unknown || (!used && searched) {
+                    violations.push(Violation::new(unused_disable,
+                                                   &index,
+                                                   suppression.start,
+                                                   suppression.end,
+                                                   None));
+                }
+            }
+            violations.sort_by_key(|v| (v.start, v.end));
+        }
+        if let Some(ref changes) = self.changes {
+            violations.retain(|v| changes.contains(path, v.line));
+        }
+        if let Some(ref baseline) = self.baseline {
+            baseline.filter(path, content, &mut violations);
+        }
+        violations
+    }
+}
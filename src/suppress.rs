@@ -0,0 +1,191 @@
+use regex::bytes;
+
+use search;
+
+/// Matches `relint-disable <lint>, <lint>` style directives anywhere in a line, so they work
+/// inside any language's comments.
+pub static DIRECTIVE: &'static str = r"relint-(disable-next-line|disable-line|disable|enable)\b(?:[ \t]+(\w[\w-]*(?:[ \t]*,[ \t]*\w[\w-]*)*))?";
+
+#[derive(Debug)]
+#[derive(Clone, Copy)]
+#[derive(Eq, PartialEq)]
+enum Kind {
+    /// `relint-disable-next-line`
+    NextLine,
+    /// `relint-disable-line`
+    Line,
+    /// `relint-disable` until a matching `relint-enable`
+    Block,
+}
+
+/// An inline comment suppressing findings.
+#[derive(Debug)]
+pub struct Suppression {
+    /// Byte offset of the directive within the file
+    pub start: usize,
+    /// Byte offset of the end of the directive within the file
+    pub end: usize,
+    /// Lint names, empty for all lints
+    pub lints: Vec<String>,
+    /// First line covered, 1-based
+    first: usize,
+    /// Last line covered, 1-based
+    last: usize,
+}
+
+impl Suppression {
+    pub fn covers(&self, violation: &search::Violation) -> bool {
+        self.first <= violation.line && violation.line <= self.last &&
+        (self.lints.is_empty() || self.lints.iter().any(|l| *l == violation.lint.name))
+    }
+}
+
+pub fn directive() -> bytes::Regex {
+    bytes::Regex::new(DIRECTIVE).expect("Directive pattern is valid")
+}
+
+/// Suppression comments within `content`, ordered by position.
+///
+/// A `relint-enable` closes the open `relint-disable`s whose lints it names, or all of them if
+/// it names none.  Blocks left open run to the end of the file.
+pub fn parse(directive: &bytes::Regex,
+             content: &[u8],
+             index: &search::LineIndex)
+             -> Vec<Suppression> {
+    let mut suppressions = Vec::new();
+    let mut open: Vec<usize> = Vec::new();
+    for caps in directive.captures_iter(content) {
+        let (start, end) = caps.pos(0).expect("Group 0 is always present");
+        let (line, _) = index.position(start);
+        let lints: Vec<String> = caps.at(2)
+            .map(|names| {
+                String::from_utf8_lossy(names)
+                    .split(',')
+                    .map(|name| name.trim().to_string())
+                    .collect()
+            })
+            .unwrap_or_else(Vec::new);
+        let (kind, first, last) = match caps.at(1).expect("Group 1 is always present") {
+            b"disable-next-line" => (Kind::NextLine, line + 1, line + 1),
+            b"disable-line" => (Kind::Line, line, line),
+            b"disable" => (Kind::Block, line, usize::max_value()),
+            _ => {
+                open.retain(|&i| {
+                    let block: &mut Suppression = &mut suppressions[i];
+                    let closed = lints.is_empty() ||
+                                 (!block.lints.is_empty() &&
+                                  block.lints.iter().all(|l| lints.contains(l)));
+                    if closed {
+                        block.last = line;
+                    }
+                    !closed
+                });
+                continue;
+            }
+        };
+        if kind == Kind::Block {
+            open.push(suppressions.len());
+        }
+        suppressions.push(Suppression {
+            start: start,
+            end: end,
+            lints: lints,
+            first: first,
+            last: last,
+        });
+    }
+    suppressions
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use lints;
+    use search;
+
+    fn suppressions(content: &str) -> Vec<Suppression> {
+        let content = content.as_bytes();
+        parse(&directive(), content, &search::LineIndex::new(content))
+    }
+
+    fn lines(suppression: &Suppression) -> (usize, usize) {
+        (suppression.first, suppression.last)
+    }
+
+    fn lint(name: &str) -> lints::Lint {
+        let config = format!("[{}]\npattern = \"x\"\nmessage = \"x\"\n", name);
+        lints::TomlLintFactory::new(&config)
+            .and_then(|f| f.build_lints())
+            .expect("Test lint is valid")
+            .remove(0)
+    }
+
+    fn violation(lint: &lints::Lint, line: usize) -> search::Violation {
+        search::Violation {
+            lint: lint,
+            line: line,
+            column: 1,
+            start: 0,
+            end: 0,
+            replacement: None,
+        }
+    }
+
+    #[test]
+    fn next_line_covers_only_the_following_line() {
+        let found = suppressions("a\n// relint-disable-next-line no-todo\nb\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(lines(&found[0]), (3, 3));
+        assert_eq!(found[0].lints, vec!["no-todo".to_string()]);
+    }
+
+    #[test]
+    fn line_covers_its_own_line() {
+        let found = suppressions("a\nb // relint-disable-line old-api , no-todo\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(lines(&found[0]), (2, 2));
+        assert_eq!(found[0].lints, vec!["old-api".to_string(), "no-todo".to_string()]);
+    }
+
+    #[test]
+    fn block_runs_to_end_of_file_without_enable() {
+        let found = suppressions("a\n/* relint-disable old-api */\nb\n");
+        assert_eq!(found.len(), 1);
+        assert_eq!(lines(&found[0]), (2, usize::max_value()));
+    }
+
+    #[test]
+    fn enable_closes_only_blocks_it_fully_names() {
+        let found = suppressions("/* relint-disable old-api, no-todo */\n\
+                                  /* relint-disable old-api */\n\
+                                  /* relint-enable old-api */\n\
+                                  /* relint-enable old-api, no-todo */\n");
+        assert_eq!(found.len(), 2);
+        assert_eq!(lines(&found[0]), (1, 4));
+        assert_eq!(lines(&found[1]), (2, 3));
+    }
+
+    #[test]
+    fn named_enable_leaves_unnamed_block_open() {
+        let found = suppressions("/* relint-disable */\n\
+                                  /* relint-enable old-api */\n\
+                                  /* relint-disable no-todo */\n\
+                                  /* relint-enable */\n");
+        assert_eq!(found.len(), 2);
+        assert_eq!(lines(&found[0]), (1, 4));
+        assert_eq!(lines(&found[1]), (3, 4));
+    }
+
+    #[test]
+    fn unnamed_directive_covers_all_lints() {
+        let old_api = lint("old-api");
+        let no_todo = lint("no-todo");
+        let found = suppressions("a // relint-disable-line\nb // relint-disable-line old-api\n");
+        assert!(found[0].covers(&violation(&old_api, 1)));
+        assert!(found[0].covers(&violation(&no_todo, 1)));
+        assert!(!found[0].covers(&violation(&old_api, 2)));
+        assert!(found[1].covers(&violation(&old_api, 2)));
+        assert!(!found[1].covers(&violation(&no_todo, 2)));
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::path;
+
+/// How long to wait for more changes once one arrives, so a save touching several files
+/// triggers a single re-lint.
+#[cfg(target_os = "linux")]
+static SETTLE_MS: ::libc::c_int = 100;
+
+/// Notifies of changes to files in watched directories.
+///
+/// Watches aren't recursive, so every directory of interest needs to be added.
+#[cfg(target_os = "linux")]
+pub struct Watcher {
+    fd: ::libc::c_int,
+    dirs: BTreeMap<::libc::c_int, path::PathBuf>,
+}
+
+#[cfg(target_os = "linux")]
+impl Watcher {
+    pub fn new() -> io::Result<Watcher> {
+        let fd = unsafe { inotify::inotify_init1(inotify::IN_NONBLOCK | inotify::IN_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Watcher {
+            fd: fd,
+            dirs: BTreeMap::new(),
+        })
+    }
+
+    pub fn add(&mut self, dir: &path::Path) -> io::Result<()> {
+        use std::ffi;
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_dir = ffi::CString::new(dir.as_os_str().as_bytes())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let mask = inotify::IN_CLOSE_WRITE | inotify::IN_CREATE | inotify::IN_DELETE |
+                   inotify::IN_MOVED_FROM | inotify::IN_MOVED_TO;
+        let wd = unsafe { inotify::inotify_add_watch(self.fd, c_dir.as_ptr(), mask) };
+        if wd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        self.dirs.insert(wd, dir.to_path_buf());
+        Ok(())
+    }
+
+    /// Block until files change, returning their paths or `None` if changes were lost and
+    /// everything should be considered changed.
+    ///
+    /// After the first change, keeps collecting until none arrive for `SETTLE_MS`.
+    pub fn wait(&mut self) -> io::Result<Option<Vec<path::PathBuf>>> {
+        let mut changed = Vec::new();
+        let mut overflowed = false;
+        let mut timeout = -1;
+        while self.poll(timeout)? {
+            overflowed |= self.read(&mut changed)?;
+            if overflowed || !changed.is_empty() {
+                timeout = SETTLE_MS;
+            }
+        }
+        if overflowed {
+            return Ok(None);
+        }
+        changed.sort();
+        changed.dedup();
+        Ok(Some(changed))
+    }
+
+    /// Wait up to `timeout` milliseconds, or forever if negative, returning whether events
+    /// are ready to be read.
+    fn poll(&self, timeout: ::libc::c_int) -> io::Result<bool> {
+        let mut fds = ::libc::pollfd {
+            fd: self.fd,
+            events: ::libc::POLLIN,
+            revents: 0,
+        };
+        loop {
+            let ready = unsafe { ::libc::poll(&mut fds, 1, timeout) };
+            if ready >= 0 {
+                return Ok(ready > 0);
+            }
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Read pending events into `changed`, returning whether the event queue overflowed.
+    fn read(&mut self, changed: &mut Vec<path::PathBuf>) -> io::Result<bool> {
+        use std::ffi;
+        use std::mem;
+        use std::ptr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut buf = [0u8; 4096];
+        let len = unsafe {
+            ::libc::read(self.fd,
+                         buf.as_mut_ptr() as *mut ::libc::c_void,
+                         buf.len() as ::libc::size_t)
+        };
+        if len < 0 {
+            let err = io::Error::last_os_error();
+            if err.kind() == io::ErrorKind::WouldBlock {
+                return Ok(false);
+            }
+            return Err(err);
+        }
+
+        let len = len as usize;
+        let header = mem::size_of::<inotify::inotify_event>();
+        let mut overflowed = false;
+        let mut offset = 0;
+        while offset + header <= len {
+            let mut event: inotify::inotify_event = unsafe { mem::zeroed() };
+            unsafe {
+                ptr::copy_nonoverlapping(buf[offset..].as_ptr(),
+                                         &mut event as *mut _ as *mut u8,
+                                         header);
+            }
+            let name = &buf[offset + header..offset + header + event.len as usize];
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+            offset += header + event.len as usize;
+
+            if event.mask & inotify::IN_Q_OVERFLOW != 0 {
+                overflowed = true;
+            } else if event.mask & inotify::IN_IGNORED != 0 {
+                self.dirs.remove(&event.wd);
+            } else if let Some(dir) = self.dirs.get(&event.wd) {
+                changed.push(dir.join(ffi::OsStr::from_bytes(name)));
+            }
+        }
+        Ok(overflowed)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        unsafe {
+            ::libc::close(self.fd);
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[allow(non_camel_case_types)]
+mod inotify {
+    use libc::{c_char, c_int};
+
+    // The same as for `open`, which differ between architectures
+    pub const IN_NONBLOCK: c_int = ::libc::O_NONBLOCK;
+    pub const IN_CLOEXEC: c_int = ::libc::O_CLOEXEC;
+
+    pub const IN_CLOSE_WRITE: u32 = 0x0000_0008;
+    pub const IN_MOVED_FROM: u32 = 0x0000_0040;
+    pub const IN_MOVED_TO: u32 = 0x0000_0080;
+    pub const IN_CREATE: u32 = 0x0000_0100;
+    pub const IN_DELETE: u32 = 0x0000_0200;
+    pub const IN_Q_OVERFLOW: u32 = 0x0000_4000;
+    pub const IN_IGNORED: u32 = 0x0000_8000;
+
+    #[repr(C)]
+    pub struct inotify_event {
+        pub wd: c_int,
+        pub mask: u32,
+        pub cookie: u32,
+        pub len: u32,
+    }
+
+    extern "C" {
+        pub fn inotify_init1(flags: c_int) -> c_int;
+        pub fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
+    }
+}
+
+/// Notifies of changes to files in watched directories.
+#[cfg(not(target_os = "linux"))]
+pub struct Watcher;
+
+#[cfg(not(target_os = "linux"))]
+impl Watcher {
+    pub fn new() -> io::Result<Watcher> {
+        Err(io::Error::new(io::ErrorKind::Other, "Watching is only supported on Linux"))
+    }
+
+    pub fn add(&mut self, _dir: &path::Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    pub fn wait(&mut self) -> io::Result<Option<Vec<path::PathBuf>>> {
+        Ok(None)
+    }
+}